@@ -0,0 +1,19 @@
+use crate::Table;
+
+pub const SIGNATURE: &[u8; 4] = b"FACP";
+
+#[derive(Debug, Copy, Clone)]
+pub struct Fadt {
+    pub century: Option<u8>
+}
+
+impl Fadt {
+    pub fn parse(table: &Table) -> Fadt {
+        Fadt {
+            century: match table.read_u8(108) {
+                Some(0) | None => None,
+                Some(register) => Some(register)
+            }
+        }
+    }
+}
@@ -0,0 +1,31 @@
+use crate::Table;
+
+pub const SIGNATURE: &[u8; 4] = b"HPET";
+
+const SYSTEM_MEMORY: u8 = 0;
+
+#[derive(Debug, Copy, Clone)]
+pub struct Hpet {
+    pub block_id: u32,
+    pub base: usize,
+    pub number: u8,
+    pub min_tick: u16
+}
+
+impl Hpet {
+    pub fn parse(table: &Table) -> Option<Hpet> {
+        if table.read_u8(40) != Some(SYSTEM_MEMORY) {
+            return None;
+        }
+        let base = match table.read_u64(44) {
+            Some(0) | None => return None,
+            Some(base) => base as usize
+        };
+        Some(Hpet {
+            block_id: table.read_u32(36).unwrap_or(0),
+            base: base,
+            number: table.read_u8(52).unwrap_or(0),
+            min_tick: table.read_u16(53).unwrap_or(0)
+        })
+    }
+}
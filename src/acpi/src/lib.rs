@@ -0,0 +1,205 @@
+#![no_std]
+
+extern crate alloc;
+
+pub mod fadt;
+pub mod hpet;
+pub mod madt;
+
+use mem::paging;
+
+use multiboot2::rsdp::Rsdp;
+
+use spinlock::Mutex;
+
+use alloc::vec::Vec;
+
+use core::ptr;
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+const HEADER_SIZE: usize = 36;
+const PAGE_SIZE: usize = 0x1000;
+
+static ROOT: AtomicUsize = AtomicUsize::new(0);
+static EXTENDED: AtomicBool = AtomicBool::new(false);
+// The MMIO window is never given back, so every range is mapped only once and
+// later lookups reuse it.
+static MAPPINGS: Mutex<Vec<Mapping>> = Mutex::new(Vec::new());
+
+struct Mapping {
+    phys: usize,
+    len: usize,
+    virt: usize
+}
+
+#[derive(Debug)]
+pub enum AcpiError {
+    NoRsdp,
+    InvalidRsdp,
+    NotFound([u8; 4]),
+    InvalidTable([u8; 4]),
+    Map(paging::Error)
+}
+
+pub struct Table {
+    addr: usize,
+    pub signature: [u8; 4],
+    pub length: usize,
+    pub revision: u8
+}
+
+impl Table {
+    fn map(phys: usize) -> Result<Table, AcpiError> {
+        let header = match map(phys, HEADER_SIZE) {
+            Ok(addr) => addr,
+            Err(error) => return Err(error)
+        };
+        let mut signature = [0; 4];
+        signature.copy_from_slice(unsafe { core::slice::from_raw_parts(header as *const u8, 4) });
+        let length = unsafe { ptr::read_unaligned((header + 4) as *const u32) } as usize;
+        if length < HEADER_SIZE {
+            return Err(AcpiError::InvalidTable(signature));
+        }
+        let addr = match map(phys, length) {
+            Ok(addr) => addr,
+            Err(error) => return Err(error)
+        };
+        let table = Table {
+            addr: addr,
+            signature: signature,
+            length: length,
+            revision: unsafe { *((addr + 8) as *const u8) }
+        };
+        match table.data().iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte)) {
+            0 => Ok(table),
+            _ => Err(AcpiError::InvalidTable(signature))
+        }
+    }
+
+    fn header(phys: usize) -> Result<[u8; 4], AcpiError> {
+        let header = match map(phys, 4) {
+            Ok(addr) => addr,
+            Err(error) => return Err(error)
+        };
+        let mut signature = [0; 4];
+        signature.copy_from_slice(unsafe { core::slice::from_raw_parts(header as *const u8, 4) });
+        Ok(signature)
+    }
+
+    pub fn data(&self) -> &'static [u8] {
+        unsafe { core::slice::from_raw_parts(self.addr as *const u8, self.length) }
+    }
+
+    pub fn read_u8(&self, offset: usize) -> Option<u8> {
+        self.data().get(offset).cloned()
+    }
+
+    pub fn read_u16(&self, offset: usize) -> Option<u16> {
+        self.read(offset, 2).map(|value| value as u16)
+    }
+
+    pub fn read_u32(&self, offset: usize) -> Option<u32> {
+        self.read(offset, 4).map(|value| value as u32)
+    }
+
+    pub fn read_u64(&self, offset: usize) -> Option<u64> {
+        self.read(offset, 8)
+    }
+
+    fn read(&self, offset: usize, len: usize) -> Option<u64> {
+        match self.data().get(offset..offset + len) {
+            Some(bytes) => Some(bytes.iter().rev().fold(0, |value, byte| value << 8 | *byte as u64)),
+            None => None
+        }
+    }
+}
+
+// Maps whole pages, so that a header mapping usually covers its table as well.
+fn map(phys: usize, len: usize) -> Result<usize, AcpiError> {
+    let mut mappings = MAPPINGS.lock();
+    if let Some(mapping) = mappings.iter()
+        .find(|mapping| mapping.phys <= phys && phys + len <= mapping.phys + mapping.len) {
+        return Ok(mapping.virt + (phys - mapping.phys));
+    }
+    let start = phys & !(PAGE_SIZE - 1);
+    let end = (phys + len + PAGE_SIZE - 1) & !(PAGE_SIZE - 1);
+    let virt = match mem::mmio::map_memory(start, end - start) {
+        Ok(virt) => virt,
+        Err(error) => return Err(AcpiError::Map(error))
+    };
+    mappings.push(Mapping {
+        phys: start,
+        len: end - start,
+        virt: virt
+    });
+    Ok(virt + (phys - start))
+}
+
+pub fn init(rsdp: Option<&Rsdp>) -> Result<(), AcpiError> {
+    let rsdp = match rsdp {
+        Some(rsdp) => rsdp,
+        None => return Err(AcpiError::NoRsdp)
+    };
+    if !rsdp.is_valid() {
+        return Err(AcpiError::InvalidRsdp);
+    }
+    match rsdp.xsdt_addr {
+        Some(xsdt) if xsdt != 0 => {
+            EXTENDED.store(true, Ordering::SeqCst);
+            ROOT.store(xsdt as usize, Ordering::SeqCst);
+        },
+        _ => ROOT.store(rsdp.rsdt_addr as usize, Ordering::SeqCst)
+    }
+    Ok(())
+}
+
+pub fn find(signature: &[u8; 4]) -> Result<Table, AcpiError> {
+    let root = match ROOT.load(Ordering::SeqCst) {
+        0 => return Err(AcpiError::NoRsdp),
+        root => match Table::map(root) {
+            Ok(table) => table,
+            Err(error) => return Err(error)
+        }
+    };
+    let entry_size = match EXTENDED.load(Ordering::SeqCst) {
+        true => 8,
+        false => 4
+    };
+    for index in 0..(root.length - HEADER_SIZE) / entry_size {
+        let offset = HEADER_SIZE + index * entry_size;
+        let phys = match root.read(offset, entry_size) {
+            Some(phys) => phys as usize,
+            None => break
+        };
+        match Table::header(phys) {
+            Ok(found) if &found == signature => return Table::map(phys),
+            Ok(_) => {},
+            Err(error) => return Err(error)
+        }
+    }
+    Err(AcpiError::NotFound(*signature))
+}
+
+pub fn madt() -> Result<madt::Madt, AcpiError> {
+    match find(madt::SIGNATURE) {
+        Ok(table) => Ok(madt::Madt::parse(&table)),
+        Err(error) => Err(error)
+    }
+}
+
+pub fn hpet() -> Result<hpet::Hpet, AcpiError> {
+    match find(hpet::SIGNATURE) {
+        Ok(table) => match hpet::Hpet::parse(&table) {
+            Some(hpet) => Ok(hpet),
+            None => Err(AcpiError::InvalidTable(*hpet::SIGNATURE))
+        },
+        Err(error) => Err(error)
+    }
+}
+
+pub fn fadt() -> Result<fadt::Fadt, AcpiError> {
+    match find(fadt::SIGNATURE) {
+        Ok(table) => Ok(fadt::Fadt::parse(&table)),
+        Err(error) => Err(error)
+    }
+}
@@ -0,0 +1,88 @@
+use crate::Table;
+
+use alloc::vec::Vec;
+
+pub const SIGNATURE: &[u8; 4] = b"APIC";
+
+const ENTRIES: usize = 44;
+
+const ENTRY_IO_APIC: u8 = 1;
+const ENTRY_OVERRIDE: u8 = 2;
+const ENTRY_LOCAL_APIC_ADDRESS: u8 = 5;
+
+pub const POLARITY_MASK: u16 = 0b11;
+pub const POLARITY_LOW: u16 = 0b11;
+pub const TRIGGER_MASK: u16 = 0b11 << 2;
+pub const TRIGGER_LEVEL: u16 = 0b11 << 2;
+
+#[derive(Debug, Copy, Clone)]
+pub struct IoApic {
+    pub id: u8,
+    pub addr: usize,
+    pub gsi_base: u32
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct Override {
+    pub bus: u8,
+    pub source: u8,
+    pub gsi: u32,
+    pub flags: u16
+}
+
+pub struct Madt {
+    pub local_apic: usize,
+    pub flags: u32,
+    pub io_apics: Vec<IoApic>,
+    pub overrides: Vec<Override>
+}
+
+impl Override {
+    pub fn is_active_low(&self) -> bool {
+        self.flags & POLARITY_MASK == POLARITY_LOW
+    }
+
+    pub fn is_level_triggered(&self) -> bool {
+        self.flags & TRIGGER_MASK == TRIGGER_LEVEL
+    }
+}
+
+impl Madt {
+    pub fn parse(table: &Table) -> Madt {
+        let mut madt = Madt {
+            local_apic: table.read_u32(36).unwrap_or(0) as usize,
+            flags: table.read_u32(40).unwrap_or(0),
+            io_apics: Vec::new(),
+            overrides: Vec::new()
+        };
+        let mut offset = ENTRIES;
+        while offset + 2 <= table.length {
+            let kind = table.read_u8(offset).unwrap_or(0);
+            let len = table.read_u8(offset + 1).unwrap_or(0) as usize;
+            if len < 2 || offset + len > table.length {
+                break;
+            }
+            match kind {
+                ENTRY_IO_APIC => madt.io_apics.push(IoApic {
+                    id: table.read_u8(offset + 2).unwrap_or(0),
+                    addr: table.read_u32(offset + 4).unwrap_or(0) as usize,
+                    gsi_base: table.read_u32(offset + 8).unwrap_or(0)
+                }),
+                ENTRY_OVERRIDE => madt.overrides.push(Override {
+                    bus: table.read_u8(offset + 2).unwrap_or(0),
+                    source: table.read_u8(offset + 3).unwrap_or(0),
+                    gsi: table.read_u32(offset + 4).unwrap_or(0),
+                    flags: table.read_u16(offset + 8).unwrap_or(0)
+                }),
+                ENTRY_LOCAL_APIC_ADDRESS => {
+                    if let Some(addr) = table.read_u64(offset + 4) {
+                        madt.local_apic = addr as usize;
+                    }
+                },
+                _ => {}
+            }
+            offset += len;
+        }
+        madt
+    }
+}
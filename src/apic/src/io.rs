@@ -0,0 +1,59 @@
+use core::ptr;
+
+const REGISTER_SELECT: usize = 0x00;
+const REGISTER_WINDOW: usize = 0x10;
+
+const VERSION: u32 = 0x01;
+const REDIRECTION_TABLE: u32 = 0x10;
+
+pub const ACTIVE_LOW: u32 = 1 << 13;
+pub const LEVEL_TRIGGERED: u32 = 1 << 15;
+
+const BIT_MASKED: u32 = 1 << 16;
+
+pub struct IoApic {
+    base: usize
+}
+
+impl IoApic {
+    pub const fn new(base: usize) -> IoApic {
+        IoApic {
+            base: base
+        }
+    }
+
+    unsafe fn read(&self, register: u32) -> u32 {
+        ptr::write_volatile((self.base + REGISTER_SELECT) as *mut u32, register);
+        ptr::read_volatile((self.base + REGISTER_WINDOW) as *const u32)
+    }
+
+    unsafe fn write(&self, register: u32, value: u32) {
+        ptr::write_volatile((self.base + REGISTER_SELECT) as *mut u32, register);
+        ptr::write_volatile((self.base + REGISTER_WINDOW) as *mut u32, value);
+    }
+
+    pub fn entries(&self) -> u32 {
+        unsafe { ((self.read(VERSION) >> 16) & 0xff) + 1 }
+    }
+
+    pub fn redirect(&self, gsi: u32, vector: u8, destination: u8, masked: bool, flags: u32) {
+        let mut low = vector as u32 | (flags & (ACTIVE_LOW | LEVEL_TRIGGERED));
+        if masked {
+            low |= BIT_MASKED;
+        }
+        unsafe {
+            self.write(REDIRECTION_TABLE + gsi * 2 + 1, (destination as u32) << 24);
+            self.write(REDIRECTION_TABLE + gsi * 2, low);
+        }
+    }
+
+    pub fn set_masked(&self, gsi: u32, masked: bool) {
+        unsafe {
+            let low = self.read(REDIRECTION_TABLE + gsi * 2);
+            self.write(REDIRECTION_TABLE + gsi * 2, match masked {
+                true => low | BIT_MASKED,
+                false => low & !BIT_MASKED
+            });
+        }
+    }
+}
@@ -0,0 +1,202 @@
+#![no_std]
+
+extern crate alloc;
+
+pub mod local;
+pub mod io;
+
+use crate::io::IoApic;
+use crate::local::LocalApic;
+
+use acpi::AcpiError;
+
+use idt::controller::Controller;
+use idt::interrupts;
+use mem::paging;
+
+use spinlock::Mutex;
+
+use alloc::vec::Vec;
+
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+pub static APIC: Apic = Apic::new();
+
+pub const IRQ_OFFSET: u8 = 32;
+pub const SPURIOUS_VECTOR: u8 = 0xff;
+
+const ISA_LINES: usize = 16;
+
+#[derive(Debug)]
+pub enum ApicError {
+    Unsupported,
+    NoIoApic,
+    Acpi(AcpiError),
+    Map(paging::Error)
+}
+
+#[derive(Copy, Clone)]
+struct Route {
+    gsi: u32,
+    flags: u32
+}
+
+struct Routing {
+    io_apics: Vec<(IoApic, u32, u32)>,
+    lines: [Option<Route>; ISA_LINES]
+}
+
+pub struct Apic {
+    local: AtomicUsize,
+    routing: Mutex<Routing>
+}
+
+pub fn is_supported() -> bool {
+    asm::x86_64::cpuid::has_apic()
+}
+
+impl Routing {
+    fn route(&self, line: u8) -> Option<Route> {
+        match self.lines.get(line as usize) {
+            Some(route) => *route,
+            None => Some(Route {
+                gsi: line as u32,
+                flags: 0
+            })
+        }
+    }
+
+    fn pin(&self, gsi: u32) -> Option<(&IoApic, u32)> {
+        match self.io_apics.iter().find(|(_, base, count)| gsi >= *base && gsi < base + count) {
+            Some((io, base, _)) => Some((io, gsi - base)),
+            None => None
+        }
+    }
+
+    fn set_masked(&self, line: u8, masked: bool) {
+        let route = match self.route(line) {
+            Some(route) => route,
+            None => return
+        };
+        if let Some((io, pin)) = self.pin(route.gsi) {
+            io.set_masked(pin, masked);
+        }
+    }
+}
+
+impl Apic {
+    pub const fn new() -> Apic {
+        Apic {
+            local: AtomicUsize::new(0),
+            routing: Mutex::new(Routing {
+                io_apics: Vec::new(),
+                lines: [None; ISA_LINES]
+            })
+        }
+    }
+
+    pub fn init(&self) -> Result<(), ApicError> {
+        if !is_supported() {
+            return Err(ApicError::Unsupported);
+        }
+        let madt = match acpi::madt() {
+            Ok(madt) => madt,
+            Err(error) => return Err(ApicError::Acpi(error))
+        };
+        if madt.io_apics.is_empty() {
+            return Err(ApicError::NoIoApic);
+        }
+        let mut routing = self.routing.lock();
+        let physical_base = unsafe { LocalApic::physical_base() };
+        if physical_base != madt.local_apic {
+            log::warn!("Local APIC at {:#x}, but the MADT reports {:#x}", physical_base,
+                madt.local_apic);
+        }
+        let local_base = match mem::mmio::map(physical_base, 0x1000) {
+            Ok(addr) => addr,
+            Err(error) => return Err(ApicError::Map(error))
+        };
+        for entry in madt.io_apics.iter() {
+            let io = match mem::mmio::map(entry.addr, 0x20) {
+                Ok(addr) => IoApic::new(addr),
+                Err(error) => return Err(ApicError::Map(error))
+            };
+            let count = io.entries();
+            for pin in 0..count {
+                io.redirect(pin, 0, 0, true, 0);
+            }
+            routing.io_apics.push((io, entry.gsi_base, count));
+        }
+        for line in 0..ISA_LINES {
+            let claimed = madt.overrides.iter()
+                .any(|other| other.bus == 0 && other.gsi == line as u32);
+            routing.lines[line] = match madt.overrides.iter()
+                .find(|other| other.bus == 0 && other.source as usize == line) {
+                Some(entry) => {
+                    let mut flags = 0;
+                    if entry.is_active_low() {
+                        flags |= io::ACTIVE_LOW;
+                    }
+                    if entry.is_level_triggered() {
+                        flags |= io::LEVEL_TRIGGERED;
+                    }
+                    Some(Route {
+                        gsi: entry.gsi,
+                        flags: flags
+                    })
+                },
+                None if claimed => None,
+                None => Some(Route {
+                    gsi: line as u32,
+                    flags: 0
+                })
+            };
+        }
+        self.local.store(local_base, Ordering::SeqCst);
+        let local = self.local();
+        unsafe {
+            LocalApic::global_enable();
+            local.enable(SPURIOUS_VECTOR);
+        }
+        for line in 0..ISA_LINES {
+            let route = match routing.lines[line] {
+                Some(route) => route,
+                None => continue
+            };
+            if let Some((io, pin)) = routing.pin(route.gsi) {
+                io.redirect(pin, IRQ_OFFSET + line as u8, local.id(), true, route.flags);
+            }
+        }
+        Ok(())
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.local.load(Ordering::SeqCst) != 0
+    }
+
+    pub fn local(&self) -> LocalApic {
+        LocalApic::new(self.local.load(Ordering::SeqCst))
+    }
+}
+
+impl Controller for Apic {
+    fn vector(&self, line: u8) -> usize {
+        IRQ_OFFSET as usize + line as usize
+    }
+
+    fn mask(&self, line: u8) {
+        interrupts::without(|| self.routing.lock().set_masked(line, true))
+    }
+
+    fn unmask(&self, line: u8) {
+        interrupts::without(|| self.routing.lock().set_masked(line, false))
+    }
+
+    fn eoi(&self, _vector: usize) {
+        self.local().eoi();
+    }
+
+    fn is_spurious(&self, vector: usize) -> bool {
+        vector == SPURIOUS_VECTOR as usize
+    }
+}
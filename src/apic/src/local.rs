@@ -0,0 +1,72 @@
+use asm::x86_64::reg::msr;
+
+use core::ptr;
+
+const IA32_APIC_BASE: u32 = 0x1b;
+const BIT_GLOBAL_ENABLE: u64 = 1 << 11;
+const BASE_MASK: u64 = 0x000f_ffff_ffff_f000;
+
+pub const ID: usize = 0x20;
+pub const VERSION: usize = 0x30;
+pub const TASK_PRIORITY: usize = 0x80;
+pub const EOI: usize = 0xb0;
+pub const SPURIOUS: usize = 0xf0;
+pub const ERROR_STATUS: usize = 0x280;
+pub const LVT_TIMER: usize = 0x320;
+pub const LVT_LINT0: usize = 0x350;
+pub const LVT_LINT1: usize = 0x360;
+pub const LVT_ERROR: usize = 0x370;
+pub const TIMER_INITIAL: usize = 0x380;
+pub const TIMER_CURRENT: usize = 0x390;
+pub const TIMER_DIVIDE: usize = 0x3e0;
+
+pub const BIT_SOFTWARE_ENABLE: u32 = 1 << 8;
+pub const BIT_MASKED: u32 = 1 << 16;
+
+pub struct LocalApic {
+    base: usize
+}
+
+impl LocalApic {
+    pub unsafe fn physical_base() -> usize {
+        (msr::read(IA32_APIC_BASE) & BASE_MASK) as usize
+    }
+
+    pub unsafe fn global_enable() {
+        msr::write(IA32_APIC_BASE, msr::read(IA32_APIC_BASE) | BIT_GLOBAL_ENABLE);
+    }
+
+    pub const fn new(base: usize) -> LocalApic {
+        LocalApic {
+            base: base
+        }
+    }
+
+    pub unsafe fn read(&self, register: usize) -> u32 {
+        ptr::read_volatile((self.base + register) as *const u32)
+    }
+
+    pub unsafe fn write(&self, register: usize, value: u32) {
+        ptr::write_volatile((self.base + register) as *mut u32, value);
+    }
+
+    pub unsafe fn enable(&self, spurious_vector: u8) {
+        self.write(TASK_PRIORITY, 0);
+        self.write(LVT_TIMER, BIT_MASKED);
+        self.write(LVT_LINT0, BIT_MASKED);
+        self.write(LVT_LINT1, BIT_MASKED);
+        self.write(LVT_ERROR, BIT_MASKED);
+        self.write(ERROR_STATUS, 0);
+        self.write(SPURIOUS, BIT_SOFTWARE_ENABLE | spurious_vector as u32);
+    }
+
+    pub fn id(&self) -> u8 {
+        unsafe { (self.read(ID) >> 24) as u8 }
+    }
+
+    pub fn eoi(&self) {
+        unsafe {
+            self.write(EOI, 0);
+        }
+    }
+}
@@ -0,0 +1,117 @@
+global irq_stubs
+global exception_stubs
+extern irq_dispatch
+extern exception_dispatch
+
+%define IRQ_BASE	32
+%define IRQ_COUNT	224
+%define EXCEPTION_COUNT	32
+
+%macro EXCEPTION_STUB 1
+exception_stub_%1:
+%if !((%1 == 8) || (%1 >= 10 && %1 <= 14) || (%1 == 17) || (%1 == 21) || (%1 == 29) || (%1 == 30))
+	push qword 0
+%endif
+	push qword %1
+	jmp exception_common
+%endmacro
+
+%macro IRQ_STUB 1
+irq_stub_%1:
+	push qword %1
+	jmp irq_common
+%endmacro
+
+section .text
+bits 64
+exception_common:
+	cld
+	push rax
+	push rbx
+	push rcx
+	push rdx
+	push rsi
+	push rdi
+	push rbp
+	push r8
+	push r9
+	push r10
+	push r11
+	push r12
+	push r13
+	push r14
+	push r15
+	mov rdi, rsp
+	call exception_dispatch
+	pop r15
+	pop r14
+	pop r13
+	pop r12
+	pop r11
+	pop r10
+	pop r9
+	pop r8
+	pop rbp
+	pop rdi
+	pop rsi
+	pop rdx
+	pop rcx
+	pop rbx
+	pop rax
+	add rsp, 16
+	iretq
+
+irq_common:
+	cld
+	push rax
+	push rcx
+	push rdx
+	push rsi
+	push rdi
+	push r8
+	push r9
+	push r10
+	push r11
+	mov rdi, [rsp + 9 * 8]
+	lea rsi, [rsp + 10 * 8]
+	sub rsp, 8
+	call irq_dispatch
+	add rsp, 8
+	pop r11
+	pop r10
+	pop r9
+	pop r8
+	pop rdi
+	pop rsi
+	pop rdx
+	pop rcx
+	pop rax
+	add rsp, 8
+	iretq
+
+%assign vector 0
+%rep EXCEPTION_COUNT
+EXCEPTION_STUB vector
+%assign vector vector + 1
+%endrep
+
+%assign vector IRQ_BASE
+%rep IRQ_COUNT
+IRQ_STUB vector
+%assign vector vector + 1
+%endrep
+
+section .rodata
+exception_stubs:
+%assign vector 0
+%rep EXCEPTION_COUNT
+	dq exception_stub_ %+ vector
+%assign vector vector + 1
+%endrep
+
+irq_stubs:
+%assign vector IRQ_BASE
+%rep IRQ_COUNT
+	dq irq_stub_ %+ vector
+%assign vector vector + 1
+%endrep
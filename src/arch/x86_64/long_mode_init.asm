@@ -17,4 +17,5 @@ long_mode_start:
 	or rax, 0b11
 	mov [pml4_table + 511 * 8], rax
 
+	xor rbp, rbp
 	call koop
@@ -6,9 +6,38 @@ pub mod instruction {
     pub unsafe fn hlt() {
         asm!("hlt");
     }
+
+    pub unsafe fn rdtsc() -> u64 {
+        let low: u32;
+        let high: u32;
+        asm!("rdtsc" : "={eax}"(low), "={edx}"(high) ::: "volatile");
+        (high as u64) << 32 | low as u64
+    }
+
+    pub unsafe fn cli() {
+        asm!("cli" :::: "volatile");
+    }
+
+    pub unsafe fn sti() {
+        asm!("sti" :::: "volatile");
+    }
 }
 
 pub mod reg {
+    pub mod msr {
+        pub unsafe fn read(id: u32) -> u64 {
+            let low: u32;
+            let high: u32;
+            asm!("rdmsr" : "={eax}"(low), "={edx}"(high) : "{ecx}"(id) :: "volatile");
+            (high as u64) << 32 | low as u64
+        }
+
+        pub unsafe fn write(id: u32, value: u64) {
+            asm!("wrmsr" :: "{ecx}"(id), "{eax}"(value as u32), "{edx}"((value >> 32) as u32)
+                 :: "volatile");
+        }
+    }
+
     pub mod efer {
         const ID: usize = 0xC0000080;
 
@@ -22,6 +51,48 @@ pub mod reg {
         }
     }
 
+    pub mod rflags {
+        pub const BIT_INTERRUPT: usize = 1 << 9;
+
+        pub unsafe fn read() -> usize {
+            let value: usize;
+            asm!("pushfq; popq $0" : "=r"(value) :: "memory" : "volatile");
+            value
+        }
+    }
+
+    pub mod cr0 {
+        pub unsafe fn read() -> usize {
+            let value: usize;
+            asm!("mov %cr0, %rax" : "={rax}"(value) ::: "volatile");
+            value
+        }
+    }
+
+    pub mod cr2 {
+        pub unsafe fn read() -> usize {
+            let value: usize;
+            asm!("mov %cr2, %rax" : "={rax}"(value) ::: "volatile");
+            value
+        }
+    }
+
+    pub mod cr3 {
+        pub unsafe fn read() -> usize {
+            let value: usize;
+            asm!("mov %cr3, %rax" : "={rax}"(value) ::: "volatile");
+            value
+        }
+    }
+
+    pub mod cr4 {
+        pub unsafe fn read() -> usize {
+            let value: usize;
+            asm!("mov %cr4, %rax" : "={rax}"(value) ::: "volatile");
+            value
+        }
+    }
+
     pub mod tlb {
         pub unsafe fn flush() {
             let mut value: usize;
@@ -29,12 +100,57 @@ pub mod reg {
             asm!("mov %rax, %cr3" :: "{rax}"(value) ::: "volatile"); 
         }
 
+        pub unsafe fn invalidate(addr: usize) {
+            asm!("invlpg ($0)" :: "r"(addr) : "memory" : "volatile");
+        }
+
         pub unsafe fn update(new_value: usize) {
             asm!("mov %rax, %cr3" :: "{rax}"((new_value & !0xfff)) ::: "volatile");
         }
     }
 }
 
+pub mod cpuid {
+    use core::arch::x86_64::__cpuid;
+
+    const LEAF_EXTENDED_MAX: u32 = 0x8000_0000;
+    const LEAF_EXTENDED_FEATURES: u32 = 0x8000_0001;
+
+    const LEAF_FEATURES: u32 = 0x1;
+
+    const BIT_PDPE1GB: u32 = 1 << 26;
+    const BIT_APIC: u32 = 1 << 9;
+    const BIT_TSC: u32 = 1 << 4;
+    const LEAF_ADVANCED_POWER: u32 = 0x8000_0007;
+    const BIT_INVARIANT_TSC: u32 = 1 << 8;
+
+    pub fn max_extended_leaf() -> u32 {
+        unsafe { __cpuid(LEAF_EXTENDED_MAX).eax }
+    }
+
+    pub fn has_apic() -> bool {
+        unsafe { __cpuid(LEAF_FEATURES).edx & BIT_APIC != 0 }
+    }
+
+    pub fn has_tsc() -> bool {
+        unsafe { __cpuid(LEAF_FEATURES).edx & BIT_TSC != 0 }
+    }
+
+    pub fn has_invariant_tsc() -> bool {
+        if max_extended_leaf() < LEAF_ADVANCED_POWER {
+            return false;
+        }
+        unsafe { __cpuid(LEAF_ADVANCED_POWER).edx & BIT_INVARIANT_TSC != 0 }
+    }
+
+    pub fn has_1g_pages() -> bool {
+        if max_extended_leaf() < LEAF_EXTENDED_FEATURES {
+            return false;
+        }
+        unsafe { __cpuid(LEAF_EXTENDED_FEATURES).edx & BIT_PDPE1GB != 0 }
+    }
+}
+
 pub mod mmio {
     pub struct Port {
         addr: usize
@@ -0,0 +1,74 @@
+use multiboot2::elf::SymbolTable;
+
+use core::fmt;
+
+const MAX_DEPTH: usize = 32;
+
+static mut SYMBOLS: Option<SymbolTable> = None;
+
+struct Demangle(&'static str);
+
+impl fmt::Display for Demangle {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut rest = match self.0.starts_with("_ZN") {
+            true => &self.0[3..],
+            false => return write!(f, "{}", self.0)
+        };
+        let mut first = true;
+        while !rest.starts_with('E') {
+            let digits = rest.bytes().take_while(|byte| byte.is_ascii_digit()).count();
+            let len = match rest[..digits].parse::<usize>() {
+                Ok(len) if digits + len <= rest.len() => len,
+                _ => return write!(f, "{}", self.0)
+            };
+            let part = &rest[digits..digits + len];
+            rest = &rest[digits + len..];
+            if rest.starts_with('E') && part.len() == 17 && part.starts_with('h') {
+                break;
+            }
+            if !first {
+                write!(f, "::")?;
+            }
+            write!(f, "{}", part)?;
+            first = false;
+        }
+        Ok(())
+    }
+}
+
+pub fn init(mb2: usize) {
+    unsafe {
+        SYMBOLS = multiboot2::Info::new(mb2).get_symbol_table();
+    }
+}
+
+pub fn print(w: &mut dyn fmt::Write) -> fmt::Result {
+    let mut rbp: usize;
+    unsafe {
+        asm!("mov %rbp, $0" : "=r"(rbp) ::: "volatile");
+    }
+    writeln!(w, "Backtrace:")?;
+    for depth in 0..MAX_DEPTH {
+        if rbp == 0 || rbp % 8 != 0 || !is_canonical(rbp) {
+            break;
+        }
+        let ip = unsafe { *((rbp + 8) as *const usize) };
+        if ip == 0 {
+            break;
+        }
+        match unsafe { SYMBOLS }.and_then(|symbols| symbols.resolve(ip - 1)) {
+            Some((name, offset)) => writeln!(w, "  #{:<2} {:#018x} {}+{:#x}", depth, ip,
+                Demangle(name), offset + 1)?,
+            None => writeln!(w, "  #{:<2} {:#018x} ?", depth, ip)?
+        }
+        rbp = unsafe { *(rbp as *const usize) };
+    }
+    Ok(())
+}
+
+fn is_canonical(addr: usize) -> bool {
+    match addr & (1 << 47) {
+        0 => addr >> 48 == 0,
+        _ => addr >> 48 == 0xffff
+    }
+}
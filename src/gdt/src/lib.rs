@@ -0,0 +1,139 @@
+#![no_std]
+#![feature(asm)]
+
+use mem::kstack;
+
+use spinlock::Mutex;
+
+use core::cell::UnsafeCell;
+use core::marker::{Send, Sync};
+use core::mem::size_of;
+
+pub static GDT: GDT_ = GDT_::new();
+
+pub const KERNEL_CODE: u16 = 1 << 3;
+pub const KERNEL_DATA: u16 = 2 << 3;
+pub const TSS: u16 = 3 << 3;
+
+pub const DOUBLE_FAULT_IST: u16 = 1;
+pub const NMI_IST: u16 = 2;
+pub const MACHINE_CHECK_IST: u16 = 3;
+
+const IST_PAGES: usize = 4;
+
+const BIT_ACCESSED: u64 = 1 << 40;
+const BIT_WRITABLE: u64 = 1 << 41;
+const BIT_EXECUTABLE: u64 = 1 << 43;
+const BIT_USER_SEGMENT: u64 = 1 << 44;
+const BIT_PRESENT: u64 = 1 << 47;
+const BIT_LONG_MODE: u64 = 1 << 53;
+const BITS_TSS_AVAILABLE: u64 = 0b1001 << 40;
+
+const CODE_SEGMENT: u64 = BIT_ACCESSED | BIT_EXECUTABLE | BIT_USER_SEGMENT | BIT_PRESENT
+    | BIT_LONG_MODE;
+const DATA_SEGMENT: u64 = BIT_ACCESSED | BIT_WRITABLE | BIT_USER_SEGMENT | BIT_PRESENT;
+
+#[repr(C, packed)]
+pub struct TaskStateSegment {
+    _res0: u32,
+    pub privilege_stacks: [u64; 3],
+    _res1: u64,
+    pub interrupt_stacks: [u64; 7],
+    _res2: u64,
+    _res3: u16,
+    pub iomap_base: u16
+}
+
+impl TaskStateSegment {
+    pub const fn new() -> TaskStateSegment {
+        TaskStateSegment {
+            _res0: 0,
+            privilege_stacks: [0; 3],
+            _res1: 0,
+            interrupt_stacks: [0; 7],
+            _res2: 0,
+            _res3: 0,
+            iomap_base: size_of::<TaskStateSegment>() as u16
+        }
+    }
+}
+
+#[repr(C, packed)]
+struct GDTR {
+    size: u16,
+    ptr: usize
+}
+
+pub struct GDT_ {
+    entries: UnsafeCell<[u64; 8]>,
+    tss: UnsafeCell<TaskStateSegment>,
+    mutex: Mutex<()>
+}
+
+impl GDT_ {
+    pub const fn new() -> GDT_ {
+        GDT_ {
+            entries: UnsafeCell::new([0; 8]),
+            tss: UnsafeCell::new(TaskStateSegment::new()),
+            mutex: Mutex::new(())
+        }
+    }
+
+    pub fn init(&self) {
+        let _lock = self.mutex.lock();
+        unsafe {
+            for index in [DOUBLE_FAULT_IST, NMI_IST, MACHINE_CHECK_IST].iter() {
+                self.set_interrupt_stack(*index);
+            }
+            let entries = &mut *self.entries.get();
+            entries[(KERNEL_CODE >> 3) as usize] = CODE_SEGMENT;
+            entries[(KERNEL_DATA >> 3) as usize] = DATA_SEGMENT;
+            let (low, high) = tss_descriptor(self.tss.get() as u64);
+            entries[(TSS >> 3) as usize] = low;
+            entries[(TSS >> 3) as usize + 1] = high;
+            self.load();
+        }
+    }
+
+    unsafe fn set_interrupt_stack(&self, index: u16) {
+        let stack = match kstack::alloc(IST_PAGES) {
+            Ok(stack) => stack,
+            Err(error) => panic!("Unable to allocate interrupt stack {}: {:?}", index, error)
+        };
+        (*self.tss.get()).interrupt_stacks[index as usize - 1] = stack.top() as u64;
+        core::mem::forget(stack);
+    }
+
+    unsafe fn load(&self) {
+        let gdt_r = GDTR {
+            size: (size_of::<[u64; 8]>() - 1) as u16,
+            ptr: self.entries.get() as usize
+        };
+        asm!("lgdt ($0)" :: "r" (&gdt_r as *const GDTR) : "memory" : "volatile");
+        asm!("pushq $0
+              leaq 1f(%rip), %rax
+              pushq %rax
+              lretq
+              1:"
+             :: "r"(KERNEL_CODE as u64) : "rax", "memory" : "volatile");
+        asm!("mov $0, %ss
+              mov $0, %ds
+              mov $0, %es"
+             :: "r"(KERNEL_DATA) :: "volatile");
+        asm!("ltr $0" :: "r"(TSS) :: "volatile");
+    }
+}
+
+fn tss_descriptor(base: u64) -> (u64, u64) {
+    let limit = (size_of::<TaskStateSegment>() - 1) as u64;
+    let low = (limit & 0xffff)
+        | (base & 0xff_ffff) << 16
+        | BITS_TSS_AVAILABLE
+        | BIT_PRESENT
+        | (limit >> 16 & 0xf) << 48
+        | (base >> 24 & 0xff) << 56;
+    (low, base >> 32)
+}
+
+unsafe impl Send for GDT_ {}
+unsafe impl Sync for GDT_ {}
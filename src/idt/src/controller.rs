@@ -0,0 +1,11 @@
+pub trait Controller: Sync {
+    fn vector(&self, line: u8) -> usize;
+
+    fn mask(&self, line: u8);
+
+    fn unmask(&self, line: u8);
+
+    fn eoi(&self, vector: usize);
+
+    fn is_spurious(&self, vector: usize) -> bool;
+}
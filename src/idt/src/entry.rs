@@ -43,6 +43,10 @@ impl Entry {
         self.selector = cs;
     }
 
+    pub fn set_stack_index(&mut self, index: u16) {
+        self.options = (self.options & !BITS_STACK) | (index & BITS_STACK);
+    }
+
     pub fn set_present(&mut self, present: bool) {
         if present {
             self.options |= BIT_PRESENT;
@@ -0,0 +1,52 @@
+use core::fmt;
+
+pub use mem::fault::ErrorCode as PageFaultErrorCode;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum DescriptorTable {
+    GDT,
+    IDT,
+    LDT
+}
+
+#[derive(Copy, Clone, PartialEq)]
+pub struct SelectorErrorCode(usize);
+
+impl SelectorErrorCode {
+    pub const fn from_bits(bits: usize) -> SelectorErrorCode {
+        SelectorErrorCode(bits)
+    }
+
+    pub const fn bits(&self) -> usize {
+        self.0
+    }
+
+    pub fn is_external(&self) -> bool {
+        self.0 & 1 != 0
+    }
+
+    pub fn table(&self) -> DescriptorTable {
+        match (self.0 >> 1) & 0b11 {
+            0b00 => DescriptorTable::GDT,
+            0b10 => DescriptorTable::LDT,
+            _ => DescriptorTable::IDT
+        }
+    }
+
+    pub fn index(&self) -> usize {
+        (self.0 >> 3) & 0x1fff
+    }
+}
+
+impl fmt::Debug for SelectorErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.0 == 0 {
+            return write!(f, "0x0 (no selector)");
+        }
+        write!(f, "{:#x} ({:?}[{}]", self.0, self.table(), self.index())?;
+        if self.is_external() {
+            write!(f, ", external")?;
+        }
+        write!(f, ")")
+    }
+}
@@ -1,40 +1,137 @@
-use crate::StackFrame;
+use crate::{ExceptionFrame, StackFrame};
+use crate::error::{PageFaultErrorCode, SelectorErrorCode};
 
-fn dump_int_stack_frame(sf: &StackFrame) {
-    vga::println!("ip: {:x}", sf.ip);
-    vga::println!("cs: {:x}", sf.ip);
-    vga::println!("rflags: {:b}", sf.rflags);
-    vga::println!("sp: {:x}", sf.sp);
-    vga::println!("ss: {:x}", sf.ss);
+use mem::fault;
+
+use asm::x86_64::reg;
+
+use core::fmt::{self, Write};
+
+pub const EXCEPTION_COUNT: usize = 32;
+
+extern "C" {
+    pub(crate) static exception_stubs: [usize; EXCEPTION_COUNT];
 }
 
-pub extern "x86-interrupt" fn syscall(_sf: &mut StackFrame) {
-    vga::println!("Syscall handler");
+const NAMES: [&str; EXCEPTION_COUNT] = [
+    "Divide error",
+    "Debug exception",
+    "Non maskable interrupt",
+    "Breakpoint",
+    "Overflow",
+    "Bound range exceeded",
+    "Invalid opcode",
+    "Device not available",
+    "Double fault",
+    "Coprocessor segment overrun",
+    "Invalid TSS",
+    "Segment not present",
+    "Stack segment fault",
+    "General protection fault",
+    "Page fault",
+    "Reserved exception",
+    "x87 floating point exception",
+    "Alignment check",
+    "Machine check",
+    "SIMD floating point exception",
+    "Virtualization exception",
+    "Control protection exception",
+    "Reserved exception",
+    "Reserved exception",
+    "Reserved exception",
+    "Reserved exception",
+    "Reserved exception",
+    "Reserved exception",
+    "Hypervisor injection exception",
+    "VMM communication exception",
+    "Security exception",
+    "Reserved exception"
+];
+
+#[no_mangle]
+pub extern "C" fn exception_dispatch(ef: &mut ExceptionFrame) {
+    match ef.vector {
+        0x3 => breakpoint(ef),
+        0xe => page_fault(ef),
+        _ => fatal(ef)
+    }
 }
 
-pub extern "x86-interrupt" fn page_fault(sf: &mut StackFrame, err: usize) {
-    vga::println!("Page fault in kernel. Stopping exection");
-    dump_int_stack_frame(sf);
-    vga::println!("error code: {:x}", err);
-    unsafe {
-        asm::x86_64::instruction::hlt();
+fn mode(sf: &StackFrame) -> &'static str {
+    match sf.cs & 3 {
+        0 => "kernel",
+        _ => "user mode"
     }
 }
 
-pub extern "x86-interrupt" fn general_protection_fault(sf: &mut StackFrame, err: usize) {
-    vga::println!("General protection fault in kernel. Stopping exection");
-    dump_int_stack_frame(sf);
-    vga::println!("error code: {:x}", err);
-    unsafe {
-        asm::x86_64::instruction::hlt();
+// Exceptions are not masked by cli, so the interrupted code may hold any of the
+// log locks. Reports go through the lock-free writer, and the panic handler then
+// flushes them to the serial port.
+fn banner(w: &mut dyn fmt::Write, ef: &ExceptionFrame) -> fmt::Result {
+    writeln!(w, "{} in {}. Stopping execution", NAMES[ef.vector % EXCEPTION_COUNT],
+        mode(&ef.frame))
+}
+
+fn stop(ef: &ExceptionFrame) -> ! {
+    panic!("Unhandled exception {:#x}", ef.vector);
+}
+
+fn fatal(ef: &ExceptionFrame) -> ! {
+    let w = &mut log::Emergency;
+    let _ = banner(w, ef);
+    // Overflowing into a guard page leaves no room to push the page fault
+    // frame, so it only ever shows up here as a double fault.
+    if ef.vector == 0x8 {
+        let addr = unsafe { reg::cr2::read() };
+        if mem::kstack::is_guard(addr) {
+            let _ = writeln!(w, "Kernel stack overflow at {:#x}", addr);
+        }
     }
+    let _ = dump(w, ef);
+    let _ = match ef.vector {
+        0xa | 0xb | 0xc | 0xd => {
+            writeln!(w, "error code: {:?}", SelectorErrorCode::from_bits(ef.error))
+        },
+        0x8 | 0x11 | 0x15 | 0x1d | 0x1e => writeln!(w, "error code: {:#x}", ef.error),
+        _ => Ok(())
+    };
+    stop(ef);
 }
 
-pub extern "x86-interrupt" fn double_fault(sf: &mut StackFrame, err: usize) {
-    vga::println!("Double fault in kernel. Stopping execution");
-    dump_int_stack_frame(sf);
-    vga::println!("error code: {:x}", err);
+fn dump(w: &mut dyn fmt::Write, ef: &ExceptionFrame) -> fmt::Result {
+    let sf = &ef.frame;
+    let regs = &ef.regs;
+    writeln!(w, "ip: {:#018x}  cs: {:#06x}  rflags: {:#b}", sf.ip, sf.cs, sf.rflags)?;
+    writeln!(w, "sp: {:#018x}  ss: {:#06x}", sf.sp, sf.ss)?;
+    writeln!(w, "rax: {:#018x}  rbx: {:#018x}  rcx: {:#018x}", regs.rax, regs.rbx, regs.rcx)?;
+    writeln!(w, "rdx: {:#018x}  rsi: {:#018x}  rdi: {:#018x}", regs.rdx, regs.rsi, regs.rdi)?;
+    writeln!(w, "rbp: {:#018x}  r8:  {:#018x}  r9:  {:#018x}", regs.rbp, regs.r8, regs.r9)?;
+    writeln!(w, "r10: {:#018x}  r11: {:#018x}  r12: {:#018x}", regs.r10, regs.r11, regs.r12)?;
+    writeln!(w, "r13: {:#018x}  r14: {:#018x}  r15: {:#018x}", regs.r13, regs.r14, regs.r15)?;
     unsafe {
-        asm::x86_64::instruction::hlt();
+        writeln!(w, "cr0: {:#018x}  cr2: {:#018x}", reg::cr0::read(), reg::cr2::read())?;
+        writeln!(w, "cr3: {:#018x}  cr4: {:#018x}", reg::cr3::read(), reg::cr4::read())
+    }
+}
+
+pub extern "x86-interrupt" fn syscall(_sf: &mut StackFrame) {
+    log::trace!("Syscall handler");
+}
+
+fn breakpoint(ef: &mut ExceptionFrame) {
+    log::info!("Breakpoint at {:#x}", ef.frame.ip);
+}
+
+fn page_fault(ef: &mut ExceptionFrame) {
+    let addr = unsafe { reg::cr2::read() };
+    let code = PageFaultErrorCode::from_bits(ef.error);
+    if fault::resolve(addr, code) {
+        return;
     }
+    let w = &mut log::Emergency;
+    let _ = banner(w, ef);
+    let _ = dump(w, ef);
+    let _ = writeln!(w, "address: {:#x}", addr);
+    let _ = writeln!(w, "error code: {:?}", code);
+    stop(ef);
 }
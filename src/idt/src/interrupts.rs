@@ -0,0 +1,30 @@
+use asm::x86_64::{instruction, reg};
+
+pub fn enable() {
+    unsafe {
+        instruction::sti();
+    }
+}
+
+pub fn disable() {
+    unsafe {
+        instruction::cli();
+    }
+}
+
+pub fn are_enabled() -> bool {
+    unsafe { reg::rflags::read() & reg::rflags::BIT_INTERRUPT != 0 }
+}
+
+pub fn without<F, T>(f: F) -> T
+    where F: FnOnce() -> T {
+    let enabled = are_enabled();
+    if enabled {
+        disable();
+    }
+    let result = f();
+    if enabled {
+        enable();
+    }
+    result
+}
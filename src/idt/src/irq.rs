@@ -0,0 +1,172 @@
+use crate::{IDT, IDT_, StackFrame};
+use crate::controller::Controller;
+use crate::interrupts;
+
+use alloc::sync::Arc;
+
+use core::fmt::Write;
+use core::sync::atomic::Ordering;
+
+pub const IRQ_BASE: usize = 32;
+pub const IRQ_COUNT: usize = 224;
+pub const SYSCALL_VECTOR: usize = 0x80;
+pub const MAX_SHARED: usize = 8;
+
+type IrqFunc = Arc<dyn Fn(&mut StackFrame) + Send + Sync>;
+
+extern "C" {
+    pub(crate) static irq_stubs: [usize; IRQ_COUNT];
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum IrqError {
+    InvalidVector(usize),
+    VectorTaken(usize),
+    NotRegistered(usize),
+    NoController
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct HandlerId {
+    pub vector: usize,
+    id: usize
+}
+
+pub(crate) struct Handler {
+    vector: usize,
+    line: Option<u8>,
+    id: usize,
+    shared: bool,
+    func: IrqFunc
+}
+
+impl IDT_ {
+    pub fn register_irq<F>(&self, vector: usize, shared: bool, func: F)
+        -> Result<HandlerId, IrqError>
+        where F: Fn(&mut StackFrame) + Send + Sync + 'static {
+        self.register(vector, None, shared, Arc::new(func))
+    }
+
+    fn register(&self, vector: usize, line: Option<u8>, shared: bool, func: IrqFunc)
+        -> Result<HandlerId, IrqError> {
+        if vector < IRQ_BASE || vector >= IRQ_BASE + IRQ_COUNT || vector == SYSCALL_VECTOR {
+            return Err(IrqError::InvalidVector(vector));
+        }
+        let handler = Handler {
+            vector: vector,
+            line: line,
+            id: self.next_irq_id.fetch_add(1, Ordering::Relaxed),
+            shared: shared,
+            func: func
+        };
+        interrupts::without(|| {
+            let mut handlers = self.irq_handlers.lock();
+            if handlers.iter().any(|other| other.vector == vector
+                && (!shared || !other.shared)) {
+                return Err(IrqError::VectorTaken(vector));
+            }
+            if handlers.iter().filter(|other| other.vector == vector).count() >= MAX_SHARED {
+                return Err(IrqError::VectorTaken(vector));
+            }
+            let id = HandlerId {
+                vector: vector,
+                id: handler.id
+            };
+            handlers.push(handler);
+            Ok(id)
+        })
+    }
+
+    pub fn set_controller(&self, controller: &'static dyn Controller) {
+        interrupts::without(|| {
+            *self.controller.lock() = Some(controller);
+        });
+    }
+
+    pub fn controller(&self) -> Option<&'static dyn Controller> {
+        interrupts::without(|| *self.controller.lock())
+    }
+
+    pub fn register_line<F>(&self, line: u8, shared: bool, func: F)
+        -> Result<HandlerId, IrqError>
+        where F: Fn(&mut StackFrame) + Send + Sync + 'static {
+        let controller = match self.controller() {
+            Some(controller) => controller,
+            None => return Err(IrqError::NoController)
+        };
+        match self.register(controller.vector(line), Some(line), shared, Arc::new(func)) {
+            Ok(id) => {
+                controller.unmask(line);
+                Ok(id)
+            },
+            Err(error) => Err(error)
+        }
+    }
+
+    pub fn unregister_irq(&self, id: HandlerId) -> Result<(), IrqError> {
+        let handler = interrupts::without(|| {
+            let mut handlers = self.irq_handlers.lock();
+            let handler = match handlers.iter().position(|handler| handler.id == id.id) {
+                Some(index) => handlers.remove(index),
+                None => return Err(IrqError::NotRegistered(id.vector))
+            };
+            let last = !handlers.iter().any(|other| other.vector == handler.vector);
+            Ok((handler, last))
+        });
+        let (handler, last) = match handler {
+            Ok(handler) => handler,
+            Err(error) => return Err(error)
+        };
+        if let (Some(line), true) = (handler.line, last) {
+            if let Some(controller) = self.controller() {
+                controller.mask(line);
+            }
+        }
+        Ok(())
+    }
+
+    pub fn is_irq_registered(&self, vector: usize) -> bool {
+        interrupts::without(|| {
+            self.irq_handlers.lock().iter().any(|handler| handler.vector == vector)
+        })
+    }
+
+    fn dispatch(&self, vector: usize, sf: &mut StackFrame) {
+        let controller = *self.controller.lock();
+        if let Some(controller) = controller {
+            if controller.is_spurious(vector) {
+                return;
+            }
+        }
+        self.run_handlers(vector, sf);
+        if let Some(controller) = controller {
+            controller.eoi(vector);
+        }
+    }
+
+    // Handlers run without the handler list locked, so that they may register
+    // or unregister handlers themselves.
+    fn run_handlers(&self, vector: usize, sf: &mut StackFrame) {
+        let mut funcs: [Option<IrqFunc>; MAX_SHARED] = Default::default();
+        {
+            let handlers = self.irq_handlers.lock();
+            let matching = handlers.iter().filter(|handler| handler.vector == vector);
+            for (slot, handler) in funcs.iter_mut().zip(matching) {
+                *slot = Some(handler.func.clone());
+            }
+        }
+        if funcs[0].is_none() {
+            let _ = writeln!(log::Emergency, "Unhandled interrupt {:#x} at {:#x}", vector,
+                sf.ip);
+            return;
+        }
+        for func in funcs.iter().filter_map(|func| func.as_ref()) {
+            func(sf);
+        }
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn irq_dispatch(vector: usize, sf: &mut StackFrame) {
+    IDT.dispatch(vector, sf);
+}
@@ -2,18 +2,31 @@
 #![feature(abi_x86_interrupt)]
 #![feature(asm)]
 
+extern crate alloc;
+
 mod entry;
+pub mod controller;
+pub mod error;
 pub mod handlers;
+pub mod interrupts;
+pub mod irq;
 
 use crate::entry::Entry;
 
+use crate::controller::Controller;
+use crate::irq::{IRQ_BASE, IRQ_COUNT, SYSCALL_VECTOR};
+
 use spinlock::Mutex;
 
+use alloc::vec::Vec;
+
 use core::cell::UnsafeCell;
 use core::marker::{Send, Sync};
+use core::sync::atomic::AtomicUsize;
 
 pub static IDT: IDT_ = IDT_::new();
 
+#[repr(C)]
 pub struct StackFrame {
     pub ip: u64,
     pub cs: u64,
@@ -22,13 +35,42 @@ pub struct StackFrame {
     pub ss: u64
 }
 
+#[repr(C)]
+pub struct Registers {
+    pub r15: u64,
+    pub r14: u64,
+    pub r13: u64,
+    pub r12: u64,
+    pub r11: u64,
+    pub r10: u64,
+    pub r9: u64,
+    pub r8: u64,
+    pub rbp: u64,
+    pub rdi: u64,
+    pub rsi: u64,
+    pub rdx: u64,
+    pub rcx: u64,
+    pub rbx: u64,
+    pub rax: u64
+}
+
+#[repr(C)]
+pub struct ExceptionFrame {
+    pub regs: Registers,
+    pub vector: usize,
+    pub error: usize,
+    pub frame: StackFrame
+}
+
 type HandlerFunc = extern "x86-interrupt" fn (&mut StackFrame);
-type HandlerFuncError = extern "x86-interrupt" fn (&mut StackFrame, usize);
 type IDTPtr = *const [Entry; 256];
 
 #[repr(C)]
 pub struct IDT_ {
     entries: UnsafeCell<[Entry; 256]>,
+    irq_handlers: Mutex<Vec<irq::Handler>>,
+    controller: Mutex<Option<&'static dyn Controller>>,
+    next_irq_id: AtomicUsize,
     mutex: Mutex<()>
 }
 
@@ -42,6 +84,9 @@ impl IDT_ {
     pub const fn new() -> IDT_ {
         IDT_ {
             entries: UnsafeCell::new([Entry::new_empty(); 256]),
+            irq_handlers: Mutex::new(Vec::new()),
+            controller: Mutex::new(None),
+            next_irq_id: AtomicUsize::new(0),
             mutex: Mutex::new(())
         }
     }
@@ -49,10 +94,16 @@ impl IDT_ {
     pub fn init(&self) {
         let _lock = self.mutex.lock();
         unsafe {
-            self.set_handler(0x80, handlers::syscall);
-            self.set_handler_with_error(0xd, handlers::general_protection_fault);
-            self.set_handler_with_error(0xe, handlers::page_fault);
-            self.set_handler_with_error(0x8, handlers::double_fault);
+            for index in 0..handlers::EXCEPTION_COUNT {
+                self.set_stub(index, handlers::exception_stubs[index]);
+            }
+            for index in IRQ_BASE..IRQ_BASE + IRQ_COUNT {
+                self.set_stub(index, irq::irq_stubs[index - IRQ_BASE]);
+            }
+            self.set_handler(SYSCALL_VECTOR, handlers::syscall);
+            self.set_stack_index(0x8, gdt::DOUBLE_FAULT_IST);
+            self.set_stack_index(0x2, gdt::NMI_IST);
+            self.set_stack_index(0x12, gdt::MACHINE_CHECK_IST);
             self.load(self.entries.get());
         }
     }
@@ -72,12 +123,18 @@ impl IDT_ {
         (*entries)[index].set_cs();
     }
 
-    unsafe fn set_handler_with_error(&self, index: usize, handler: HandlerFuncError) {
+    unsafe fn set_stub(&self, index: usize, stub: usize) {
         let entries = self.entries.get();
-        (*entries)[index].set_addr(handler as usize);
+        (*entries)[index].set_addr(stub);
         (*entries)[index].set_present(true);
         (*entries)[index].set_cs();
     }
+
+    unsafe fn set_stack_index(&self, index: usize, stack: u16) {
+        let entries = self.entries.get();
+        (*entries)[index].set_stack_index(stack);
+    }
+
 }
 
 unsafe impl Send for IDT_ {}
@@ -0,0 +1,135 @@
+use asm::x86_64::mmio;
+
+const DATA: usize = 0x60;
+const STATUS: usize = 0x64;
+const COMMAND: usize = 0x64;
+
+const STATUS_OUTPUT_FULL: u8 = 1 << 0;
+const STATUS_INPUT_FULL: u8 = 1 << 1;
+
+const READ_CONFIG: u8 = 0x20;
+const WRITE_CONFIG: u8 = 0x60;
+const DISABLE_SECOND_PORT: u8 = 0xa7;
+const DISABLE_FIRST_PORT: u8 = 0xad;
+const ENABLE_FIRST_PORT: u8 = 0xae;
+const SELF_TEST: u8 = 0xaa;
+const TEST_FIRST_PORT: u8 = 0xab;
+
+const SELF_TEST_PASSED: u8 = 0x55;
+const PORT_TEST_PASSED: u8 = 0x00;
+
+const CONFIG_FIRST_IRQ: u8 = 1 << 0;
+const CONFIG_SECOND_IRQ: u8 = 1 << 1;
+const CONFIG_TRANSLATION: u8 = 1 << 6;
+
+const DEVICE_ENABLE_SCANNING: u8 = 0xf4;
+const DEVICE_ACK: u8 = 0xfa;
+
+const TIMEOUT: usize = 100_000;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum ControllerError {
+    Timeout,
+    SelfTest(u8),
+    PortTest(u8),
+    NoAck(u8)
+}
+
+unsafe fn wait_input() -> Result<(), ControllerError> {
+    for _ in 0..TIMEOUT {
+        if mmio::Port::new(STATUS).read() & STATUS_INPUT_FULL == 0 {
+            return Ok(());
+        }
+    }
+    Err(ControllerError::Timeout)
+}
+
+unsafe fn wait_output() -> Result<(), ControllerError> {
+    for _ in 0..TIMEOUT {
+        if mmio::Port::new(STATUS).read() & STATUS_OUTPUT_FULL != 0 {
+            return Ok(());
+        }
+    }
+    Err(ControllerError::Timeout)
+}
+
+unsafe fn command(value: u8) -> Result<(), ControllerError> {
+    match wait_input() {
+        Ok(()) => {
+            mmio::Port::new(COMMAND).write(value);
+            Ok(())
+        },
+        Err(error) => Err(error)
+    }
+}
+
+unsafe fn write(value: u8) -> Result<(), ControllerError> {
+    match wait_input() {
+        Ok(()) => {
+            mmio::Port::new(DATA).write(value);
+            Ok(())
+        },
+        Err(error) => Err(error)
+    }
+}
+
+unsafe fn read() -> Result<u8, ControllerError> {
+    match wait_output() {
+        Ok(()) => Ok(mmio::Port::new(DATA).read()),
+        Err(error) => Err(error)
+    }
+}
+
+unsafe fn query(value: u8) -> Result<u8, ControllerError> {
+    match command(value) {
+        Ok(()) => read(),
+        Err(error) => Err(error)
+    }
+}
+
+pub fn read_data() -> u8 {
+    unsafe { mmio::Port::new(DATA).read() }
+}
+
+pub unsafe fn init() -> Result<(), ControllerError> {
+    if let Err(error) = command(DISABLE_FIRST_PORT) {
+        return Err(error);
+    }
+    if let Err(error) = command(DISABLE_SECOND_PORT) {
+        return Err(error);
+    }
+    while mmio::Port::new(STATUS).read() & STATUS_OUTPUT_FULL != 0 {
+        mmio::Port::new(DATA).read();
+    }
+    let config = match query(READ_CONFIG) {
+        Ok(config) => config & !(CONFIG_FIRST_IRQ | CONFIG_SECOND_IRQ),
+        Err(error) => return Err(error)
+    };
+    match query(SELF_TEST) {
+        Ok(SELF_TEST_PASSED) => {},
+        Ok(result) => return Err(ControllerError::SelfTest(result)),
+        Err(error) => return Err(error)
+    }
+    match query(TEST_FIRST_PORT) {
+        Ok(PORT_TEST_PASSED) => {},
+        Ok(result) => return Err(ControllerError::PortTest(result)),
+        Err(error) => return Err(error)
+    }
+    if let Err(error) = command(WRITE_CONFIG) {
+        return Err(error);
+    }
+    if let Err(error) = write(config | CONFIG_FIRST_IRQ | CONFIG_TRANSLATION) {
+        return Err(error);
+    }
+    if let Err(error) = command(ENABLE_FIRST_PORT) {
+        return Err(error);
+    }
+    if let Err(error) = write(DEVICE_ENABLE_SCANNING) {
+        return Err(error);
+    }
+    match read() {
+        Ok(DEVICE_ACK) => Ok(()),
+        Ok(result) => Err(ControllerError::NoAck(result)),
+        Err(error) => Err(error)
+    }
+}
@@ -0,0 +1,80 @@
+#![no_std]
+
+pub mod controller;
+pub mod scancode;
+mod ring;
+
+pub use crate::controller::ControllerError;
+pub use crate::scancode::{Event, KeyCode, Modifiers};
+
+use crate::ring::Ring;
+use crate::scancode::Decoder;
+
+use idt::{IDT, StackFrame};
+use idt::irq::IrqError;
+
+use core::cell::UnsafeCell;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+pub const IRQ_LINE: u8 = 1;
+
+static EVENTS: Ring = Ring::new();
+static DROPPED: AtomicUsize = AtomicUsize::new(0);
+static DECODER: DecoderCell = DecoderCell(UnsafeCell::new(Decoder::new()));
+
+struct DecoderCell(UnsafeCell<Decoder>);
+
+unsafe impl Sync for DecoderCell {}
+
+#[derive(Debug)]
+pub enum KeyboardError {
+    Controller(ControllerError),
+    Irq(IrqError)
+}
+
+pub fn init() -> Result<(), KeyboardError> {
+    if let Err(error) = unsafe { controller::init() } {
+        return Err(KeyboardError::Controller(error));
+    }
+    match IDT.register_line(IRQ_LINE, false, handle_irq) {
+        Ok(_) => Ok(()),
+        Err(error) => Err(KeyboardError::Irq(error))
+    }
+}
+
+fn handle_irq(_sf: &mut StackFrame) {
+    let byte = controller::read_data();
+    let event = unsafe { (*DECODER.0.get()).feed(byte) };
+    if let Some(event) = event {
+        if !EVENTS.push(event) {
+            DROPPED.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+pub fn poll() -> Option<Event> {
+    EVENTS.pop()
+}
+
+pub fn wait() -> Event {
+    loop {
+        if let Some(event) = poll() {
+            return event;
+        }
+        unsafe {
+            asm::x86_64::instruction::hlt();
+        }
+    }
+}
+
+pub fn read_char() -> char {
+    loop {
+        if let Some(c) = wait().ch {
+            return c;
+        }
+    }
+}
+
+pub fn dropped() -> usize {
+    DROPPED.load(Ordering::Relaxed)
+}
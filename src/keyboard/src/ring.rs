@@ -0,0 +1,51 @@
+use crate::scancode::Event;
+
+use core::cell::UnsafeCell;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+pub const CAPACITY: usize = 128;
+
+pub struct Ring {
+    buffer: UnsafeCell<[Option<Event>; CAPACITY]>,
+    head: AtomicUsize,
+    tail: AtomicUsize
+}
+
+impl Ring {
+    pub const fn new() -> Ring {
+        Ring {
+            buffer: UnsafeCell::new([None; CAPACITY]),
+            head: AtomicUsize::new(0),
+            tail: AtomicUsize::new(0)
+        }
+    }
+
+    pub fn push(&self, value: Event) -> bool {
+        let tail = self.tail.load(Ordering::Relaxed);
+        let next = (tail + 1) % CAPACITY;
+        if next == self.head.load(Ordering::Acquire) {
+            return false;
+        }
+        unsafe {
+            (*self.buffer.get())[tail] = Some(value);
+        }
+        self.tail.store(next, Ordering::Release);
+        true
+    }
+
+    pub fn pop(&self) -> Option<Event> {
+        let head = self.head.load(Ordering::Relaxed);
+        if head == self.tail.load(Ordering::Acquire) {
+            return None;
+        }
+        let value = unsafe { (*self.buffer.get())[head] };
+        self.head.store((head + 1) % CAPACITY, Ordering::Release);
+        value
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.head.load(Ordering::Acquire) == self.tail.load(Ordering::Acquire)
+    }
+}
+
+unsafe impl Sync for Ring {}
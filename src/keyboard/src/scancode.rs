@@ -0,0 +1,261 @@
+const EXTENDED: u8 = 0xe0;
+const PAUSE: u8 = 0xe1;
+const RELEASED: u8 = 0x80;
+
+const ACK: u8 = 0xfa;
+const RESEND: u8 = 0xfe;
+const ECHO: u8 = 0xee;
+const SELF_TEST_PASSED: u8 = 0xaa;
+const SELF_TEST_FAILED: [u8; 2] = [0xfc, 0xfd];
+const ERRORS: [u8; 2] = [0x00, 0xff];
+const FAKE_SHIFTS: [u8; 2] = [0x2a, 0x36];
+
+const NORMAL: &[u8; 0x3a] = b"\x00\x1b1234567890-=\x08\tqwertyuiop[]\n\x00asdfghjkl;'`\x00\\zxcvbnm,./\x00*\x00 ";
+const SHIFTED: &[u8; 0x3a] = b"\x00\x1b!@#$%^&*()_+\x08\tQWERTYUIOP{}\n\x00ASDFGHJKL:\"~\x00|ZXCVBNM<>?\x00*\x00 ";
+const KEYPAD: &[u8; 13] = b"789-456+1230.";
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum KeyCode {
+    Char(char),
+    Keypad(char),
+    Escape,
+    Backspace,
+    Tab,
+    Enter,
+    Space,
+    LeftShift,
+    RightShift,
+    LeftCtrl,
+    RightCtrl,
+    LeftAlt,
+    RightAlt,
+    CapsLock,
+    NumLock,
+    ScrollLock,
+    Function(u8),
+    Up,
+    Down,
+    Left,
+    Right,
+    Home,
+    End,
+    PageUp,
+    PageDown,
+    Insert,
+    Delete,
+    Pause,
+    KeypadEnter,
+    Unknown(u8)
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Modifiers {
+    pub left_shift: bool,
+    pub right_shift: bool,
+    pub left_ctrl: bool,
+    pub right_ctrl: bool,
+    pub left_alt: bool,
+    pub right_alt: bool,
+    pub caps_lock: bool,
+    pub num_lock: bool
+}
+
+impl Modifiers {
+    pub const fn new() -> Modifiers {
+        Modifiers {
+            left_shift: false,
+            right_shift: false,
+            left_ctrl: false,
+            right_ctrl: false,
+            left_alt: false,
+            right_alt: false,
+            caps_lock: false,
+            num_lock: false
+        }
+    }
+
+    pub fn shift(&self) -> bool {
+        self.left_shift || self.right_shift
+    }
+
+    pub fn ctrl(&self) -> bool {
+        self.left_ctrl || self.right_ctrl
+    }
+
+    pub fn alt(&self) -> bool {
+        self.left_alt || self.right_alt
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Event {
+    pub code: KeyCode,
+    pub pressed: bool,
+    pub modifiers: Modifiers,
+    pub ch: Option<char>
+}
+
+pub struct Decoder {
+    extended: bool,
+    pause: usize,
+    modifiers: Modifiers
+}
+
+impl Decoder {
+    pub const fn new() -> Decoder {
+        Decoder {
+            extended: false,
+            pause: 0,
+            modifiers: Modifiers::new()
+        }
+    }
+
+    pub fn feed(&mut self, byte: u8) -> Option<Event> {
+        if self.is_reply(byte) {
+            return None;
+        }
+        // Pause sends E1 1D 45 when pressed and E1 9D C5 when released.
+        if self.pause > 0 {
+            self.pause -= 1;
+            if self.pause > 0 {
+                return None;
+            }
+            return Some(self.event(KeyCode::Pause, byte & RELEASED == 0, byte));
+        }
+        match byte {
+            EXTENDED => {
+                self.extended = true;
+                return None;
+            },
+            PAUSE => {
+                self.pause = 2;
+                return None;
+            },
+            _ => {}
+        }
+        let extended = self.extended;
+        self.extended = false;
+        // The fake shifts wrapped around PrintScreen and the navigation keys.
+        if extended && FAKE_SHIFTS.contains(&(byte & !RELEASED)) {
+            return None;
+        }
+        let pressed = byte & RELEASED == 0;
+        let code = match extended {
+            true => decode_extended(byte & !RELEASED),
+            false => decode(byte & !RELEASED)
+        };
+        Some(self.event(code, pressed, byte))
+    }
+
+    // 0xaa is also the release of the left shift, so it is only taken as the
+    // self-test reply when that key is not down.
+    fn is_reply(&self, byte: u8) -> bool {
+        match byte {
+            ACK | RESEND | ECHO => true,
+            SELF_TEST_PASSED => !self.extended && !self.modifiers.left_shift,
+            _ => SELF_TEST_FAILED.contains(&byte) || ERRORS.contains(&byte)
+        }
+    }
+
+    fn event(&mut self, code: KeyCode, pressed: bool, byte: u8) -> Event {
+        self.update_modifiers(code, pressed);
+        Event {
+            code: code,
+            pressed: pressed,
+            modifiers: self.modifiers,
+            ch: match pressed {
+                true => self.translate(code, byte & !RELEASED),
+                false => None
+            }
+        }
+    }
+
+    fn update_modifiers(&mut self, code: KeyCode, pressed: bool) {
+        match code {
+            KeyCode::LeftShift => self.modifiers.left_shift = pressed,
+            KeyCode::RightShift => self.modifiers.right_shift = pressed,
+            KeyCode::LeftCtrl => self.modifiers.left_ctrl = pressed,
+            KeyCode::RightCtrl => self.modifiers.right_ctrl = pressed,
+            KeyCode::LeftAlt => self.modifiers.left_alt = pressed,
+            KeyCode::RightAlt => self.modifiers.right_alt = pressed,
+            KeyCode::CapsLock if pressed => self.modifiers.caps_lock = !self.modifiers.caps_lock,
+            KeyCode::NumLock if pressed => self.modifiers.num_lock = !self.modifiers.num_lock,
+            _ => {}
+        }
+    }
+
+    fn translate(&self, code: KeyCode, scancode: u8) -> Option<char> {
+        match code {
+            KeyCode::Char(c) => {
+                let mut shift = self.modifiers.shift();
+                if c.is_ascii_alphabetic() && self.modifiers.caps_lock {
+                    shift = !shift;
+                }
+                let c = match shift {
+                    true => SHIFTED[scancode as usize] as char,
+                    false => c
+                };
+                match self.modifiers.ctrl() && c.is_ascii_alphabetic() {
+                    true => Some(((c.to_ascii_lowercase() as u8) - b'a' + 1) as char),
+                    false => Some(c)
+                }
+            },
+            KeyCode::Keypad(c) => match c.is_ascii_digit() || c == '.' {
+                true if !self.modifiers.num_lock => None,
+                _ => Some(c)
+            },
+            KeyCode::Escape => Some('\x1b'),
+            KeyCode::Backspace => Some('\x08'),
+            KeyCode::Tab => Some('\t'),
+            KeyCode::Enter | KeyCode::KeypadEnter => Some('\n'),
+            KeyCode::Space => Some(' '),
+            _ => None
+        }
+    }
+}
+
+fn decode(scancode: u8) -> KeyCode {
+    match scancode {
+        0x01 => KeyCode::Escape,
+        0x0e => KeyCode::Backspace,
+        0x0f => KeyCode::Tab,
+        0x1c => KeyCode::Enter,
+        0x1d => KeyCode::LeftCtrl,
+        0x2a => KeyCode::LeftShift,
+        0x36 => KeyCode::RightShift,
+        0x37 => KeyCode::Keypad('*'),
+        0x38 => KeyCode::LeftAlt,
+        0x39 => KeyCode::Space,
+        0x3a => KeyCode::CapsLock,
+        0x3b..=0x44 => KeyCode::Function(scancode - 0x3b + 1),
+        0x45 => KeyCode::NumLock,
+        0x46 => KeyCode::ScrollLock,
+        0x47..=0x53 => KeyCode::Keypad(KEYPAD[(scancode - 0x47) as usize] as char),
+        0x57 => KeyCode::Function(11),
+        0x58 => KeyCode::Function(12),
+        _ if (scancode as usize) < NORMAL.len() && NORMAL[scancode as usize] != 0 => {
+            KeyCode::Char(NORMAL[scancode as usize] as char)
+        },
+        _ => KeyCode::Unknown(scancode)
+    }
+}
+
+fn decode_extended(scancode: u8) -> KeyCode {
+    match scancode {
+        0x1c => KeyCode::KeypadEnter,
+        0x1d => KeyCode::RightCtrl,
+        0x35 => KeyCode::Keypad('/'),
+        0x38 => KeyCode::RightAlt,
+        0x47 => KeyCode::Home,
+        0x48 => KeyCode::Up,
+        0x49 => KeyCode::PageUp,
+        0x4b => KeyCode::Left,
+        0x4d => KeyCode::Right,
+        0x4f => KeyCode::End,
+        0x50 => KeyCode::Down,
+        0x51 => KeyCode::PageDown,
+        0x52 => KeyCode::Insert,
+        0x53 => KeyCode::Delete,
+        _ => KeyCode::Unknown(scancode)
+    }
+}
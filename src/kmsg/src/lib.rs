@@ -0,0 +1,137 @@
+#![no_std]
+
+use core::cell::UnsafeCell;
+use core::fmt;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+pub const SIZE: usize = 1 << 16;
+pub const MAX_MESSAGE: usize = 512;
+
+// STATE packs the number of writers in flight above the reserved position, so
+// the last writer to finish can publish everything reserved before it without
+// ever waiting on another writer.
+const PENDING_SHIFT: u32 = 48;
+const PENDING: usize = 1 << PENDING_SHIFT;
+const POSITION_MASK: usize = PENDING - 1;
+
+static BUFFER: Buffer = Buffer(UnsafeCell::new([0; SIZE]));
+static STATE: AtomicUsize = AtomicUsize::new(0);
+static COMMITTED: AtomicUsize = AtomicUsize::new(0);
+
+struct Buffer(UnsafeCell<[u8; SIZE]>);
+
+unsafe impl Sync for Buffer {}
+
+pub struct Message {
+    bytes: [u8; MAX_MESSAGE],
+    len: usize
+}
+
+impl Message {
+    pub const fn new() -> Message {
+        Message {
+            bytes: [0; MAX_MESSAGE],
+            len: 0
+        }
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes[..self.len]
+    }
+
+    pub fn commit(&self) {
+        write(self.as_bytes());
+    }
+}
+
+impl fmt::Write for Message {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let len = s.len().min(MAX_MESSAGE - self.len);
+        self.bytes[self.len..self.len + len].copy_from_slice(&s.as_bytes()[..len]);
+        self.len += len;
+        Ok(())
+    }
+}
+
+pub fn print(args: fmt::Arguments) {
+    let mut message = Message::new();
+    let _ = fmt::Write::write_fmt(&mut message, args);
+    message.commit();
+}
+
+pub fn write(bytes: &[u8]) {
+    let bytes = match bytes.len() > SIZE {
+        true => &bytes[bytes.len() - SIZE..],
+        false => bytes
+    };
+    let start = STATE.fetch_add(PENDING + bytes.len(), Ordering::Relaxed) & POSITION_MASK;
+    for (i, byte) in bytes.iter().enumerate() {
+        unsafe {
+            (*BUFFER.0.get())[(start + i) % SIZE] = *byte;
+        }
+    }
+    let state = STATE.fetch_sub(PENDING, Ordering::AcqRel);
+    if state >> PENDING_SHIFT == 1 {
+        publish(state & POSITION_MASK);
+    }
+}
+
+fn publish(position: usize) {
+    let mut committed = COMMITTED.load(Ordering::Relaxed);
+    while committed < position {
+        match COMMITTED.compare_exchange_weak(committed, position, Ordering::Release,
+            Ordering::Relaxed) {
+            Ok(_) => return,
+            Err(current) => committed = current
+        }
+    }
+}
+
+pub fn written() -> usize {
+    COMMITTED.load(Ordering::Acquire)
+}
+
+pub fn oldest() -> usize {
+    match STATE.load(Ordering::Acquire) & POSITION_MASK {
+        reserved if reserved > SIZE => reserved - SIZE,
+        _ => 0
+    }
+}
+
+pub fn read_from(position: usize, out: &mut [u8]) -> (usize, usize) {
+    let end = written();
+    let start = position.max(oldest());
+    let len = end.saturating_sub(start).min(out.len());
+    for (i, byte) in out[..len].iter_mut().enumerate() {
+        *byte = unsafe { (*BUFFER.0.get())[(start + i) % SIZE] };
+    }
+    let valid = oldest();
+    if valid > start {
+        let lost = (valid - start).min(len);
+        out.copy_within(lost..len, 0);
+        return (len - lost, start + len);
+    }
+    (len, start + len)
+}
+
+pub fn read(out: &mut [u8]) -> usize {
+    let position = written().saturating_sub(out.len());
+    read_from(position, out).0
+}
+
+pub fn dump(w: &mut dyn fmt::Write) -> fmt::Result {
+    let mut chunk = [0u8; 256];
+    let mut position = oldest();
+    let end = written();
+    while position < end {
+        let (len, next) = read_from(position, &mut chunk);
+        for byte in chunk[..len].iter() {
+            w.write_char(*byte as char)?;
+        }
+        if next == position {
+            break;
+        }
+        position = next;
+    }
+    Ok(())
+}
@@ -1,14 +1,24 @@
 #![no_std]
 #![feature(alloc_error_handler)]
+#![feature(asm)]
 
+mod backtrace;
+
+use gdt::GDT;
 use idt::IDT;
+use idt::interrupts;
+use apic::APIC;
+use pic::PIC;
 use mem::allocator::ALLOCATOR;
-use vga::println;
+use mem::kstack;
+use serial::{ComAddr, SerialSink};
 
 extern crate alloc;
 
 use core::alloc::Layout;
+use core::fmt::Write;
 use core::panic::PanicInfo;
+use core::sync::atomic::{AtomicBool, Ordering};
 
 #[alloc_error_handler]
 fn alloc_error_handler(layout: Layout) -> ! {
@@ -19,9 +29,17 @@ fn alloc_error_handler(layout: Layout) -> ! {
     );
 }
 
+static PANICKING: AtomicBool = AtomicBool::new(false);
+
 #[panic_handler]
 fn panic(info: &PanicInfo) -> ! {
-    println!("{}", info);
+    // The panic path must not take the log lock: the panic may have been
+    // raised while it was held. A panic raised while reporting one only stops.
+    if !PANICKING.swap(true, Ordering::SeqCst) {
+        let _ = writeln!(log::Emergency, "{}", info);
+        let _ = backtrace::print(&mut log::Emergency);
+        let _ = kmsg::dump(&mut unsafe { serial::PolledWriter::new(ComAddr::Com1) });
+    }
     unsafe {
         asm::x86_64::instruction::hlt();
     }
@@ -31,11 +49,80 @@ fn panic(info: &PanicInfo) -> ! {
 #[no_mangle]
 pub fn koop(mb2: usize) -> ! {
     vga::TEXT_BUFFER.lock().clear();
+    init_log();
+    backtrace::init(mb2);
+    if let Err(error) = acpi::init(multiboot2::Info::new(mb2).get_rsdp().as_ref()) {
+        log::warn!("ACPI unavailable: {:?}", error);
+    }
     unsafe {
         ALLOCATOR.init(multiboot2::Info::new(mb2));
-        IDT.init();
+    }
+    let stack = match kstack::alloc(kstack::DEFAULT_PAGES) {
+        Ok(stack) => stack,
+        Err(error) => panic!("Unable to allocate the kernel stack: {:?}", error)
+    };
+    let top = stack.top();
+    core::mem::forget(stack);
+    unsafe {
+        asm!("mov $0, %rsp
+              xor %rbp, %rbp
+              call *$1"
+             :: "r"(top), "r"(kmain as usize) : "memory" : "volatile");
+    }
+    loop {}
+}
+
+static SERIAL: SerialSink = SerialSink::new(ComAddr::Com1);
+
+fn init_log() {
+    unsafe {
+        serial::Port::new(ComAddr::Com1);
+    }
+    for sink in [&log::VGA as &'static dyn log::Sink, &SERIAL].iter() {
+        if let Err(error) = log::add_sink(*sink) {
+            panic!("Unable to register log sink: {:?}", error);
+        }
+    }
+}
+
+extern "C" fn kmain() -> ! {
+    GDT.init();
+    IDT.init();
+    PIC.init();
+    match APIC.init() {
+        Ok(()) => {
+            PIC.disable();
+            IDT.set_controller(&APIC);
+        },
+        Err(error) => {
+            log::warn!("APIC unavailable, using the PIC: {:?}", error);
+            IDT.set_controller(&PIC)
+        }
+    }
+    if let Err(error) = time::init(time::DEFAULT_FREQUENCY) {
+        panic!("Unable to start the system timer: {:?}", error);
+    }
+    log::set_clock(time::uptime);
+    let hpet = match acpi::hpet() {
+        Ok(hpet) => Some(hpet.base),
+        Err(error) => {
+            log::info!("No HPET: {:?}", error);
+            None
+        }
+    };
+    log::info!("Clocksource: {}", time::clocksource::init(hpet));
+    let century = match acpi::fadt() {
+        Ok(fadt) => fadt.century,
+        Err(_) => None
+    };
+    log::info!("Boot time: {}", time::rtc::init(century));
+    if let Err(error) = keyboard::init() {
+        log::warn!("Keyboard unavailable: {:?}", error);
+    }
+    interrupts::enable();
+    unsafe {
         *(0xdeadbeef as *mut u8) = 42;
-        vga::println!("OK");
+        log::info!("OK");
         asm::x86_64::instruction::hlt();
     }
     loop {}
@@ -0,0 +1,214 @@
+#![no_std]
+
+pub mod sink;
+
+pub use crate::sink::{Emergency, Sink, VgaSink, VGA};
+
+use spinlock::Mutex;
+
+use core::fmt;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use core::time::Duration;
+
+const MAX_SINKS: usize = 8;
+const MAX_FILTERS: usize = 16;
+
+static LOCK: Mutex<()> = Mutex::new(());
+static SINKS: Mutex<[Option<&'static dyn Sink>; MAX_SINKS]> = Mutex::new([None; MAX_SINKS]);
+static FILTERS: Mutex<[Option<(&'static str, Level)>; MAX_FILTERS]> =
+    Mutex::new([None; MAX_FILTERS]);
+static CLOCK: Mutex<Option<fn() -> Duration>> = Mutex::new(None);
+static MAX_LEVEL: AtomicUsize = AtomicUsize::new(Level::Info as usize);
+
+#[repr(usize)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    Error = 1,
+    Warn,
+    Info,
+    Debug,
+    Trace
+}
+
+impl Level {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Level::Error => "ERROR",
+            Level::Warn => "WARN",
+            Level::Info => "INFO",
+            Level::Debug => "DEBUG",
+            Level::Trace => "TRACE"
+        }
+    }
+}
+
+impl fmt::Display for Level {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.pad(self.as_str())
+    }
+}
+
+#[derive(Debug)]
+pub enum LogError {
+    TooManySinks,
+    TooManyFilters
+}
+
+pub struct Record<'a> {
+    pub level: Level,
+    pub target: &'a str,
+    pub timestamp: Option<Duration>,
+    pub args: fmt::Arguments<'a>
+}
+
+impl<'a> Record<'a> {
+    pub fn write_to(&self, w: &mut dyn fmt::Write) -> fmt::Result {
+        if let Some(timestamp) = self.timestamp {
+            write!(w, "[{:5}.{:06}] ", timestamp.as_secs(), timestamp.subsec_micros())?;
+        }
+        write!(w, "{:<5} {}: {}\n", self.level, self.target, self.args)
+    }
+}
+
+fn without_interrupts<F, T>(f: F) -> T
+    where F: FnOnce() -> T {
+    use asm::x86_64::{instruction, reg};
+    let enabled = unsafe { reg::rflags::read() & reg::rflags::BIT_INTERRUPT != 0 };
+    if enabled {
+        unsafe {
+            instruction::cli();
+        }
+    }
+    let result = f();
+    if enabled {
+        unsafe {
+            instruction::sti();
+        }
+    }
+    result
+}
+
+pub fn add_sink(sink: &'static dyn Sink) -> Result<(), LogError> {
+    without_interrupts(|| {
+        let mut sinks = SINKS.lock();
+        match sinks.iter_mut().find(|slot| slot.is_none()) {
+            Some(slot) => {
+                *slot = Some(sink);
+                Ok(())
+            },
+            None => Err(LogError::TooManySinks)
+        }
+    })
+}
+
+pub fn set_max_level(level: Level) {
+    MAX_LEVEL.store(level as usize, Ordering::Relaxed);
+}
+
+pub fn set_filter(prefix: &'static str, level: Level) -> Result<(), LogError> {
+    without_interrupts(|| {
+        let mut filters = FILTERS.lock();
+        for slot in filters.iter_mut() {
+            match slot {
+                Some((current, _)) if *current == prefix => {
+                    *slot = Some((prefix, level));
+                    return Ok(());
+                },
+                _ => {}
+            }
+        }
+        match filters.iter_mut().find(|slot| slot.is_none()) {
+            Some(slot) => {
+                *slot = Some((prefix, level));
+                Ok(())
+            },
+            None => Err(LogError::TooManyFilters)
+        }
+    })
+}
+
+pub fn set_clock(clock: fn() -> Duration) {
+    without_interrupts(|| {
+        *CLOCK.lock() = Some(clock);
+    });
+}
+
+pub fn enabled(level: Level, target: &str) -> bool {
+    let mut max = MAX_LEVEL.load(Ordering::Relaxed);
+    let mut matched = 0;
+    for filter in FILTERS.lock().iter() {
+        if let Some((prefix, filter_level)) = filter {
+            if target.starts_with(prefix) && prefix.len() >= matched {
+                matched = prefix.len();
+                max = *filter_level as usize;
+            }
+        }
+    }
+    level as usize <= max
+}
+
+#[doc(hidden)]
+pub fn _log(level: Level, target: &str, args: fmt::Arguments) {
+    without_interrupts(|| {
+        if !enabled(level, target) {
+            return;
+        }
+        let clock = *CLOCK.lock();
+        let record = Record {
+            level: level,
+            target: target,
+            timestamp: match clock {
+                Some(clock) => Some(clock()),
+                None => None
+            },
+            args: args
+        };
+        let _lock = LOCK.lock();
+        let mut message = kmsg::Message::new();
+        let _ = record.write_to(&mut message);
+        message.commit();
+        let sinks = *SINKS.lock();
+        let mut written = false;
+        for sink in sinks.iter() {
+            if let Some(sink) = sink {
+                sink.log(&record);
+                written = true;
+            }
+        }
+        if !written {
+            VGA.log(&record);
+        }
+    })
+}
+
+#[macro_export]
+macro_rules! log {
+    ($level:expr, $($args:tt)+) => (
+        $crate::_log($level, module_path!(), format_args!($($args)+))
+    );
+}
+
+#[macro_export]
+macro_rules! error {
+    ($($args:tt)+) => ($crate::log!($crate::Level::Error, $($args)+));
+}
+
+#[macro_export]
+macro_rules! warn {
+    ($($args:tt)+) => ($crate::log!($crate::Level::Warn, $($args)+));
+}
+
+#[macro_export]
+macro_rules! info {
+    ($($args:tt)+) => ($crate::log!($crate::Level::Info, $($args)+));
+}
+
+#[macro_export]
+macro_rules! debug {
+    ($($args:tt)+) => ($crate::log!($crate::Level::Debug, $($args)+));
+}
+
+#[macro_export]
+macro_rules! trace {
+    ($($args:tt)+) => ($crate::log!($crate::Level::Trace, $($args)+));
+}
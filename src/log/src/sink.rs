@@ -0,0 +1,56 @@
+use crate::{Level, Record};
+
+use vga::{Color, TEXT_BUFFER};
+
+use core::fmt;
+
+pub static VGA: VgaSink = VgaSink;
+
+pub trait Sink: Sync {
+    fn log(&self, record: &Record);
+}
+
+pub struct VgaSink;
+
+// Writes straight to kmsg and, when it is free, to the VGA console, without
+// taking any lock that the interrupted code might hold.
+pub struct Emergency;
+
+struct ColoredWriter<'a> {
+    buffer: &'a mut vga::TextBuffer,
+    fg: Color
+}
+
+impl<'a> fmt::Write for ColoredWriter<'a> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.buffer.write(s, self.fg, Color::Black);
+        Ok(())
+    }
+}
+
+impl Sink for VgaSink {
+    fn log(&self, record: &Record) {
+        let fg = match record.level {
+            Level::Error => Color::BrightRef,
+            Level::Warn => Color::Yellow,
+            Level::Info => Color::White,
+            Level::Debug => Color::Gray,
+            Level::Trace => Color::DarkGray
+        };
+        let mut buffer = TEXT_BUFFER.lock();
+        let _ = record.write_to(&mut ColoredWriter {
+            buffer: &mut buffer,
+            fg: fg
+        });
+    }
+}
+
+impl fmt::Write for Emergency {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        kmsg::write(s.as_bytes());
+        if let Some(mut buffer) = TEXT_BUFFER.try_lock() {
+            buffer.write(s, Color::BrightRef, Color::Black);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,257 @@
+use crate::addr::Addr;
+use crate::allocator::ALLOCATOR;
+use crate::entry::{Entry, Flags};
+use crate::fault::{self, RegionKind};
+use crate::frame;
+use crate::paging::{Error, Mapper, PageSize};
+use crate::stage1;
+use crate::table::{TableLevel, PML4};
+use crate::tlb::Flush;
+
+use core::ptr;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+pub const RECURSIVE_SLOT: usize = 510;
+pub const SOURCE_SLOT: usize = 509;
+pub const TARGET_SLOT: usize = 508;
+pub const SCRATCH_SLOT: usize = 507;
+
+pub const KERNEL_LOW_SLOT: usize = 0;
+pub const USER_SLOTS_START: usize = 1;
+pub const KERNEL_SLOTS_START: usize = 256;
+
+pub const USER_START: usize = USER_SLOTS_START << 39;
+pub const USER_END: usize = KERNEL_SLOTS_START << 39;
+
+static KERNEL_ROOT: AtomicUsize = AtomicUsize::new(0);
+
+pub fn is_user_slot(slot: usize) -> bool {
+    slot >= USER_SLOTS_START && slot < KERNEL_SLOTS_START
+}
+
+pub fn pml4_addr(slot: usize) -> Addr {
+    let mut addr = Addr::new(slot << 39 | slot << 30 | slot << 21 | slot << 12);
+    addr.to_valid();
+    addr
+}
+
+pub(crate) fn set_kernel_root(frame: &frame::Frame) {
+    KERNEL_ROOT.store(frame.base.addr, Ordering::Relaxed);
+}
+
+fn scratch_addr(index: usize) -> Addr {
+    let mut addr = Addr::new(SCRATCH_SLOT << 39 | index << 12);
+    addr.to_valid();
+    addr
+}
+
+pub struct AddressSpace {
+    frame: frame::Frame
+}
+
+impl AddressSpace {
+    pub fn current() -> AddressSpace {
+        AddressSpace {
+            frame: frame::Frame::new(unsafe { asm::x86_64::reg::cr3::read() })
+        }
+    }
+
+    pub fn new() -> Result<AddressSpace, Error> {
+        ALLOCATOR.with_stage1(|stage1| {
+            let frame = match stage1.frame_allocator.alloc() {
+                Ok(frame) => frame,
+                Err(error) => return Err(Error::from(error))
+            };
+            let mut pml4 = attach(&mut stage1.pml4, &frame, TARGET_SLOT);
+            pml4.flush(0, TARGET_SLOT - 1);
+            pml4.flush(TARGET_SLOT + 1, 511);
+            pml4.set_entry(KERNEL_LOW_SLOT, stage1.pml4.get_entry(KERNEL_LOW_SLOT));
+            for slot in KERNEL_SLOTS_START..TARGET_SLOT {
+                pml4.set_entry(slot, stage1.pml4.get_entry(slot));
+            }
+            pml4.set_entry(RECURSIVE_SLOT, Entry::new(frame.base.addr,
+                    Flags::WRITABLE | Flags::PRESENT));
+            detach(&mut stage1.pml4, &mut pml4, TARGET_SLOT);
+            Ok(AddressSpace {
+                frame: frame
+            })
+        })
+    }
+
+    pub fn root(&self) -> usize {
+        self.frame.base.addr
+    }
+
+    pub fn is_active(&self) -> bool {
+        unsafe { asm::x86_64::reg::cr3::read() & !(frame::FRAME_SIZE - 1) == self.frame.base.addr }
+    }
+
+    pub unsafe fn activate(&self) {
+        asm::x86_64::reg::tlb::update(self.frame.base.addr);
+    }
+
+    pub fn with_mapper<F, T>(&self, f: F) -> Result<T, Error>
+        where F: FnOnce(&mut Mapper) -> Result<T, Error> {
+        ALLOCATOR.with_stage1(|stage1| {
+            if self.is_active() {
+                return f(&mut stage1.mapper());
+            }
+            let mut pml4 = attach(&mut stage1.pml4, &self.frame, TARGET_SLOT);
+            let result = f(&mut Mapper::new(&mut pml4, &mut stage1.frame_allocator,
+                    stage1.huge_pages));
+            detach(&mut stage1.pml4, &mut pml4, TARGET_SLOT);
+            result
+        })
+    }
+
+    pub fn try_clone(&self) -> Result<AddressSpace, Error> {
+        let child = match AddressSpace::new() {
+            Ok(child) => child,
+            Err(error) => return Err(error)
+        };
+        let regions = fault::copy_regions(self, &child);
+        let result = ALLOCATOR.with_stage1(|stage1| {
+            let mut source = match self.is_active() {
+                true => stage1.pml4,
+                false => attach(&mut stage1.pml4, &self.frame, SOURCE_SLOT)
+            };
+            let mut writer = source;
+            let mut target = attach(&mut stage1.pml4, &child.frame, TARGET_SLOT);
+            let mut result = Ok(());
+            source.for_each_leaf(0, USER_SLOTS_START, KERNEL_SLOTS_START - 1,
+                &mut |virt, entry, size| {
+                    if result.is_err() {
+                        return;
+                    }
+                    let cow = regions.iter().any(|region| {
+                        region.kind == RegionKind::CopyOnWrite && region.contains(virt.addr)
+                    });
+                    result = match cow && size == PageSize::Small {
+                        true => share_page(stage1, &mut writer, &mut target, &virt, entry),
+                        false => copy_page(stage1, &mut target, &virt, entry, size)
+                    };
+                });
+            detach(&mut stage1.pml4, &mut target, TARGET_SLOT);
+            if !self.is_active() {
+                detach(&mut stage1.pml4, &mut source, SOURCE_SLOT);
+            }
+            result
+        });
+        match result {
+            Ok(()) => Ok(child),
+            Err(error) => {
+                let _ = child.destroy();
+                Err(error)
+            }
+        }
+    }
+
+    pub fn destroy(self) -> Result<(), Error> {
+        if self.is_active() || self.root() == KERNEL_ROOT.load(Ordering::Relaxed) {
+            return Err(Error::ActiveAddressSpace);
+        }
+        fault::remove_regions(&self);
+        ALLOCATOR.with_stage1(|stage1| {
+            let mut pml4 = attach(&mut stage1.pml4, &self.frame, TARGET_SLOT);
+            pml4.release_entries(USER_SLOTS_START, KERNEL_SLOTS_START - 1,
+                &mut stage1.frame_allocator);
+            detach(&mut stage1.pml4, &mut pml4, TARGET_SLOT);
+            stage1.frame_allocator.dealloc(self.frame);
+            Ok(())
+        })
+    }
+}
+
+fn attach(active: &mut PML4, frame: &frame::Frame, slot: usize) -> PML4 {
+    active.set_entry(slot, Entry::new(frame.base.addr, Flags::WRITABLE | Flags::PRESENT));
+    unsafe {
+        asm::x86_64::reg::tlb::flush();
+    }
+    let mut view = PML4::new(&active.child_addr(slot), slot);
+    view.set_entry(slot, Entry::new(frame.base.addr, Flags::WRITABLE | Flags::PRESENT));
+    unsafe {
+        asm::x86_64::reg::tlb::flush();
+    }
+    PML4::new(&pml4_addr(slot), slot)
+}
+
+fn detach(active: &mut PML4, pml4: &mut PML4, slot: usize) {
+    pml4.set_entry(slot, Entry::new(0, Flags::NONE));
+    active.set_entry(slot, Entry::new(0, Flags::NONE));
+    unsafe {
+        asm::x86_64::reg::tlb::flush();
+    }
+}
+
+fn copy_page(stage1: &mut stage1::Allocator,
+             target: &mut PML4,
+             virt: &Addr,
+             entry: &Entry,
+             size: PageSize) -> Result<(), Error> {
+    let mut flush = Flush::new();
+    if !entry.flags.contains(Flags::PRESENT) {
+        return target.map_page(virt, Entry::from_entry(entry.value()), size,
+            &mut stage1.frame_allocator, &mut flush);
+    }
+    let count = size.size() / frame::FRAME_SIZE;
+    let new_frame = match stage1.frame_allocator.alloc_contiguous(count, size.size()) {
+        Ok(frame) => frame,
+        Err(error) => return Err(Error::from(error))
+    };
+    for i in 0..count {
+        if let Err(error) = copy_frame(stage1, entry.addr + i * frame::FRAME_SIZE,
+            new_frame.base.addr + i * frame::FRAME_SIZE) {
+            stage1.frame_allocator.dealloc_range(new_frame, count);
+            return Err(error);
+        }
+    }
+    let flags = match size {
+        PageSize::Small => entry.flags | fault::OWNED,
+        _ => entry.flags.to_small() | fault::OWNED
+    };
+    if let Err(error) = target.map_page(virt, Entry::new(new_frame.base.addr, flags), size,
+        &mut stage1.frame_allocator, &mut flush) {
+        stage1.frame_allocator.dealloc_range(new_frame, count);
+        return Err(error);
+    }
+    Ok(())
+}
+
+fn share_page(stage1: &mut stage1::Allocator,
+              source: &mut PML4,
+              target: &mut PML4,
+              virt: &Addr,
+              entry: &Entry) -> Result<(), Error> {
+    let shared = frame::Frame::new(entry.addr);
+    if !entry.flags.contains(Flags::PRESENT | fault::OWNED)
+        || !stage1.frame_allocator.share(&shared) {
+        return copy_page(stage1, target, virt, entry, PageSize::Small);
+    }
+    let mut flags = entry.flags | fault::COW;
+    flags.remove(Flags::WRITABLE);
+    let mut flush = Flush::new();
+    if let Err(error) = target.map_page(virt, Entry::new(entry.addr, flags), PageSize::Small,
+        &mut stage1.frame_allocator, &mut flush) {
+        stage1.frame_allocator.unshare(&shared);
+        return Err(error);
+    }
+    source.protect_page(virt, flags, PageSize::Small, &mut stage1.frame_allocator, &mut flush)
+}
+
+pub(crate) fn copy_frame(stage1: &mut stage1::Allocator, source: usize, target: usize)
+    -> Result<(), Error> {
+    let mut mapper = stage1.kernel_mapper();
+    if let Err(error) = mapper.map_to(scratch_addr(0), source, Flags::PRESENT) {
+        return Err(error);
+    }
+    if let Err(error) = mapper.map_to(scratch_addr(1), target,
+        Flags::PRESENT | Flags::WRITABLE) {
+        let _ = mapper.unmap_range(scratch_addr(0), frame::FRAME_SIZE);
+        return Err(error);
+    }
+    unsafe {
+        ptr::copy_nonoverlapping(scratch_addr(0).addr as *const u8,
+            scratch_addr(1).addr as *mut u8, frame::FRAME_SIZE);
+    }
+    mapper.unmap_range(scratch_addr(0), 2 * frame::FRAME_SIZE)
+}
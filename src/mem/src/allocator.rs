@@ -1,5 +1,7 @@
+use crate::stage1;
 use crate::stage2;
 use crate::addr::Addr;
+use crate::paging::{self, Mapper};
 
 use spinlock::Mutex;
 
@@ -35,6 +37,32 @@ impl<'a> Allocator<'a> {
         *self.internal.get() = Stage::Stage2(stage2::Allocator::new(mb2));
     }
 
+    pub fn mapper<F, T>(&self, f: F) -> Result<T, paging::Error>
+        where F: FnOnce(&mut Mapper) -> Result<T, paging::Error> {
+        self.with_stage1(|stage1| f(&mut stage1.mapper()))
+    }
+
+    pub(crate) fn with_stage1<F, T>(&self, f: F) -> Result<T, paging::Error>
+        where F: FnOnce(&mut stage1::Allocator) -> Result<T, paging::Error> {
+        let _lock = self.mutex.lock();
+        match unsafe { &mut *self.internal.get() } {
+            Stage::Stage2(allocator) => f(allocator.stage1()),
+            _ => Err(paging::Error::Uninitialized)
+        }
+    }
+
+    pub(crate) fn try_with_stage1<F, T>(&self, f: F) -> Result<T, paging::Error>
+        where F: FnOnce(&mut stage1::Allocator) -> Result<T, paging::Error> {
+        let _lock = match self.mutex.try_lock() {
+            Some(lock) => lock,
+            None => return Err(paging::Error::Busy)
+        };
+        match unsafe { &mut *self.internal.get() } {
+            Stage::Stage2(allocator) => f(allocator.stage1()),
+            _ => Err(paging::Error::Uninitialized)
+        }
+    }
+
     pub unsafe fn inspect(&self) {
         let _lock = self.mutex.lock();
         match &mut *self.internal.get() {
@@ -0,0 +1,78 @@
+pub const MAX_MEMORY: usize = 1 << 46;
+
+const WORD_BITS: usize = 64;
+
+pub struct Bitmap {
+    words: *mut u64,
+    frames: usize,
+    pub free: usize
+}
+
+impl Bitmap {
+    pub fn size(frames: usize) -> usize {
+        (frames + WORD_BITS - 1) / WORD_BITS * 8
+    }
+
+    pub unsafe fn new(addr: usize, frames: usize) -> Bitmap {
+        let bitmap = Bitmap {
+            words: addr as *mut u64,
+            frames: frames,
+            free: 0
+        };
+        core::ptr::write_bytes(bitmap.words, 0, Bitmap::size(frames) / 8);
+        bitmap
+    }
+
+    pub fn frames(&self) -> usize {
+        self.frames
+    }
+
+    fn word(&self, index: usize) -> u64 {
+        unsafe { *self.words.add(index / WORD_BITS) }
+    }
+
+    fn word_mut(&mut self, index: usize) -> &mut u64 {
+        unsafe { &mut *self.words.add(index / WORD_BITS) }
+    }
+
+    pub fn is_free(&self, index: usize) -> bool {
+        index < self.frames && self.word(index) & (1 << (index % WORD_BITS)) != 0
+    }
+
+    pub fn set_free(&mut self, index: usize, count: usize) {
+        for i in index..(index + count).min(self.frames) {
+            if !self.is_free(i) {
+                *self.word_mut(i) |= 1 << (i % WORD_BITS);
+                self.free += 1;
+            }
+        }
+    }
+
+    pub fn set_used(&mut self, index: usize, count: usize) {
+        for i in index..(index + count).min(self.frames) {
+            if self.is_free(i) {
+                *self.word_mut(i) &= !(1 << (i % WORD_BITS));
+                self.free -= 1;
+            }
+        }
+    }
+
+    pub fn find(&self, count: usize, align: usize, from: usize) -> Option<usize> {
+        let mut start = align_up(from, align);
+        while start + count <= self.frames {
+            if self.word(start) == 0 {
+                start = align_up((start / WORD_BITS + 1) * WORD_BITS, align);
+                continue;
+            }
+            match (start..start + count).find(|&i| !self.is_free(i)) {
+                Some(used) => start = align_up(used + 1, align),
+                None => return Some(start)
+            }
+        }
+        None
+    }
+}
+
+fn align_up(value: usize, align: usize) -> usize {
+    (value + align - 1) & !(align - 1)
+}
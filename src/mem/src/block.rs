@@ -17,7 +17,7 @@ impl Block {
     }
 
     pub fn should_map(&self, order: usize, target: usize) -> bool {
-        if self.addr + self.size() >= 0o776_000_000_000_0000 {
+        if self.addr + self.size() >= 0o774_000_000_000_0000 {
             return false;
         }
         if (order == target && target >= 12)
@@ -1,43 +1,152 @@
 use multiboot2::elf;
 
-pub const FLAG_PRESENT: usize = 1 << 0;
-pub const FLAG_WRITABLE: usize = 1 << 1;
-pub const FLAG_NO_EXEC: usize = 1 << 63;
+use core::ops::{BitAnd, BitOr, BitOrAssign, Not};
 
 const ADDR_BITS: usize = 0x000f_ffff_ffff_f000;
 const FLAG_BITS: usize = !ADDR_BITS;
 
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Flags(usize);
+
+impl Flags {
+    pub const NONE: Flags = Flags(0);
+    pub const PRESENT: Flags = Flags(1 << 0);
+    pub const WRITABLE: Flags = Flags(1 << 1);
+    pub const USER: Flags = Flags(1 << 2);
+    pub const WRITE_THROUGH: Flags = Flags(1 << 3);
+    pub const CACHE_DISABLE: Flags = Flags(1 << 4);
+    pub const ACCESSED: Flags = Flags(1 << 5);
+    pub const DIRTY: Flags = Flags(1 << 6);
+    pub const PAT: Flags = Flags(1 << 7);
+    pub const HUGE: Flags = Flags(1 << 7);
+    pub const GLOBAL: Flags = Flags(1 << 8);
+    pub const AVAILABLE_0: Flags = Flags(1 << 9);
+    pub const AVAILABLE_1: Flags = Flags(1 << 10);
+    pub const AVAILABLE_2: Flags = Flags(1 << 11);
+    pub const HUGE_PAT: Flags = Flags(1 << 12);
+    pub const AVAILABLE_HIGH: Flags = Flags(0x7f << 52);
+    pub const PROTECTION_KEY: Flags = Flags(0xf << 59);
+    pub const NO_EXEC: Flags = Flags(1 << 63);
+
+    pub const fn from_bits(bits: usize) -> Flags {
+        Flags(bits)
+    }
+
+    pub const fn bits(&self) -> usize {
+        self.0
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0 == 0
+    }
+
+    pub fn contains(&self, other: Flags) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    pub fn insert(&mut self, other: Flags) {
+        self.0 |= other.0;
+    }
+
+    pub fn remove(&mut self, other: Flags) {
+        self.0 &= !other.0;
+    }
+
+    pub fn to_huge(&self) -> Flags {
+        let mut flags = *self;
+        if flags.contains(Flags::PAT) {
+            flags.insert(Flags::HUGE_PAT);
+        }
+        flags | Flags::HUGE
+    }
+
+    pub fn to_small(&self) -> Flags {
+        let mut flags = *self;
+        flags.remove(Flags::HUGE);
+        if flags.contains(Flags::HUGE_PAT) {
+            flags.remove(Flags::HUGE_PAT);
+            flags.insert(Flags::PAT);
+        }
+        flags
+    }
+}
+
+impl BitOr for Flags {
+    type Output = Flags;
+
+    fn bitor(self, other: Flags) -> Flags {
+        Flags(self.0 | other.0)
+    }
+}
+
+impl BitOrAssign for Flags {
+    fn bitor_assign(&mut self, other: Flags) {
+        self.0 |= other.0;
+    }
+}
+
+impl BitAnd for Flags {
+    type Output = Flags;
+
+    fn bitand(self, other: Flags) -> Flags {
+        Flags(self.0 & other.0)
+    }
+}
+
+impl Not for Flags {
+    type Output = Flags;
+
+    fn not(self) -> Flags {
+        Flags(!self.0)
+    }
+}
+
 #[derive(Debug)]
 pub struct Entry {
     pub addr: usize,
-    pub flags: usize
+    pub flags: Flags
 }
 
 impl Entry {
-    pub fn new(addr: usize, flags: usize) -> Entry {
+    pub fn new(addr: usize, flags: Flags) -> Entry {
         Entry {
             addr: addr & ADDR_BITS,
-            flags: flags & FLAG_BITS
+            flags: Flags(flags.0 & FLAG_BITS)
+        }
+    }
+
+    pub fn new_huge(addr: usize, flags: Flags) -> Entry {
+        let flags = flags.to_huge();
+        Entry {
+            addr: addr & ADDR_BITS & !Flags::HUGE_PAT.0,
+            flags: Flags(flags.0 & (FLAG_BITS | Flags::HUGE_PAT.0))
         }
     }
 
     pub fn from_entry(value: usize) -> Entry {
         Entry {
             addr: value & ADDR_BITS,
-            flags: value & FLAG_BITS,
+            flags: Flags(value & FLAG_BITS),
+        }
+    }
+
+    pub fn from_huge_entry(value: usize) -> Entry {
+        Entry {
+            addr: value & ADDR_BITS & !Flags::HUGE_PAT.0,
+            flags: Flags(value & (FLAG_BITS | Flags::HUGE_PAT.0)),
         }
     }
 
     pub fn from_elf(addr: usize, elf_flags: usize) -> Entry {
-        let mut flags = 0;
+        let mut flags = Flags::NONE;
         if elf_flags & elf::SHF_ALLOC != 0 {
-            flags |= FLAG_PRESENT;
+            flags |= Flags::PRESENT;
         }
         if elf_flags & elf::SHF_WRITE != 0 {
-            flags |= FLAG_WRITABLE;
+            flags |= Flags::WRITABLE;
         }
         if elf_flags & elf::SHF_EXECINSTR == 0 {
-            flags |= FLAG_NO_EXEC;
+            flags |= Flags::NO_EXEC;
         }
         Entry {
             addr: addr & ADDR_BITS,
@@ -46,10 +155,14 @@ impl Entry {
     }
 
     pub fn unused(&self) -> bool {
-        self.addr | self.flags == 0
+        self.addr | self.flags.0 == 0
+    }
+
+    pub fn is_huge(&self) -> bool {
+        self.flags.contains(Flags::HUGE)
     }
 
     pub fn value(&self) -> usize {
-        self.addr | self.flags
+        self.addr | self.flags.0
     }
 }
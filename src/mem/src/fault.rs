@@ -0,0 +1,273 @@
+use crate::addr::Addr;
+use crate::address_space::{self, AddressSpace};
+use crate::allocator::ALLOCATOR;
+use crate::entry::Flags;
+use crate::frame;
+use crate::paging::{Error, PageSize};
+use crate::stage1;
+
+use spinlock::Mutex;
+
+use alloc::vec::Vec;
+
+use core::fmt;
+use core::ptr;
+
+pub const COW: Flags = Flags::AVAILABLE_0;
+// Set on user leaves whose frame came from the frame allocator. Only those are
+// returned to it when the address space goes away.
+pub const OWNED: Flags = Flags::AVAILABLE_1;
+
+// Regions are only modified from code touching the eagerly mapped heap, so the
+// fault handler uses try_lock and treats a held lock as an unresolved fault.
+static REGIONS: Mutex<Vec<Region>> = Mutex::new(Vec::new());
+
+#[derive(Copy, Clone, PartialEq)]
+pub struct ErrorCode(usize);
+
+impl ErrorCode {
+    pub const PRESENT: ErrorCode = ErrorCode(1 << 0);
+    pub const WRITE: ErrorCode = ErrorCode(1 << 1);
+    pub const USER: ErrorCode = ErrorCode(1 << 2);
+    pub const RESERVED_WRITE: ErrorCode = ErrorCode(1 << 3);
+    pub const INSTRUCTION_FETCH: ErrorCode = ErrorCode(1 << 4);
+    pub const PROTECTION_KEY: ErrorCode = ErrorCode(1 << 5);
+    pub const SHADOW_STACK: ErrorCode = ErrorCode(1 << 6);
+    pub const SGX: ErrorCode = ErrorCode(1 << 15);
+
+    pub const fn from_bits(bits: usize) -> ErrorCode {
+        ErrorCode(bits)
+    }
+
+    pub const fn bits(&self) -> usize {
+        self.0
+    }
+
+    pub fn contains(&self, other: ErrorCode) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+impl fmt::Debug for ErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:#x} (", self.0)?;
+        write!(f, "{}", match self.contains(ErrorCode::PRESENT) {
+            true => "protection violation",
+            false => "not present"
+        })?;
+        write!(f, ", {}", match self.contains(ErrorCode::WRITE) {
+            true => "write",
+            false => "read"
+        })?;
+        write!(f, ", {}", match self.contains(ErrorCode::USER) {
+            true => "user",
+            false => "kernel"
+        })?;
+        if self.contains(ErrorCode::RESERVED_WRITE) {
+            write!(f, ", reserved bit set")?;
+        }
+        if self.contains(ErrorCode::INSTRUCTION_FETCH) {
+            write!(f, ", instruction fetch")?;
+        }
+        if self.contains(ErrorCode::PROTECTION_KEY) {
+            write!(f, ", protection key")?;
+        }
+        if self.contains(ErrorCode::SHADOW_STACK) {
+            write!(f, ", shadow stack")?;
+        }
+        if self.contains(ErrorCode::SGX) {
+            write!(f, ", sgx")?;
+        }
+        write!(f, ")")
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum RegionKind {
+    DemandZero,
+    CopyOnWrite
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct Region {
+    pub space: usize,
+    pub start: usize,
+    pub end: usize,
+    pub kind: RegionKind,
+    pub flags: Flags
+}
+
+impl Region {
+    pub fn contains(&self, addr: usize) -> bool {
+        addr >= self.start && addr < self.end
+    }
+}
+
+pub fn add_region(space: &AddressSpace,
+                  start: Addr,
+                  len: usize,
+                  kind: RegionKind,
+                  flags: Flags) -> Result<(), Error> {
+    if start.addr & (frame::FRAME_SIZE - 1) != 0 {
+        return Err(Error::Unaligned(start.addr));
+    }
+    if len == 0 || len & (frame::FRAME_SIZE - 1) != 0 {
+        return Err(Error::InvalidSize(len));
+    }
+    let end = match start.addr.checked_add(len) {
+        Some(end) => end,
+        None => return Err(Error::InvalidSize(len))
+    };
+    if start.addr < address_space::USER_START || end > address_space::USER_END {
+        return Err(Error::Forbidden(start.addr));
+    }
+    let mut regions = REGIONS.lock();
+    let root = space.root();
+    if regions.iter().any(|region| region.space == root
+        && region.start < end && start.addr < region.end) {
+        return Err(Error::AlreadyMapped(start.addr));
+    }
+    regions.push(Region {
+        space: root,
+        start: start.addr,
+        end: end,
+        kind: kind,
+        flags: flags | Flags::PRESENT
+    });
+    Ok(())
+}
+
+pub fn remove_region(space: &AddressSpace, start: Addr) -> Result<Region, Error> {
+    let mut regions = REGIONS.lock();
+    let root = space.root();
+    match regions.iter().position(|region| region.space == root && region.start == start.addr) {
+        Some(index) => Ok(regions.remove(index)),
+        None => Err(Error::NotMapped(start.addr))
+    }
+}
+
+pub fn regions(space: &AddressSpace) -> Vec<Region> {
+    let root = space.root();
+    REGIONS.lock().iter().filter(|region| region.space == root).cloned().collect()
+}
+
+pub(crate) fn copy_regions(source: &AddressSpace, target: &AddressSpace) -> Vec<Region> {
+    let copied = regions(source);
+    let root = target.root();
+    let mut regions = REGIONS.lock();
+    for region in copied.iter() {
+        let mut region = *region;
+        region.space = root;
+        regions.push(region);
+    }
+    copied
+}
+
+pub(crate) fn remove_regions(space: &AddressSpace) {
+    let root = space.root();
+    REGIONS.lock().retain(|region| region.space != root);
+}
+
+fn find_region(space: usize, addr: usize) -> Option<Region> {
+    match REGIONS.try_lock() {
+        Some(regions) => regions.iter()
+            .find(|region| region.space == space && region.contains(addr))
+            .cloned(),
+        None => None
+    }
+}
+
+pub(crate) fn release_frame(frame_allocator: &mut frame::Allocator,
+                            frame: frame::Frame,
+                            flags: Flags) {
+    if flags.contains(COW) && frame_allocator.unshare(&frame) {
+        return;
+    }
+    frame_allocator.dealloc(frame);
+}
+
+pub fn resolve(addr: usize, code: ErrorCode) -> bool {
+    if code.contains(ErrorCode::RESERVED_WRITE) {
+        return false;
+    }
+    let space = AddressSpace::current().root();
+    let region = match find_region(space, addr) {
+        Some(region) => region,
+        None => return false
+    };
+    if code.contains(ErrorCode::USER) && !region.flags.contains(Flags::USER) {
+        return false;
+    }
+    if code.contains(ErrorCode::WRITE) && !region.flags.contains(Flags::WRITABLE) {
+        return false;
+    }
+    if code.contains(ErrorCode::INSTRUCTION_FETCH) && region.flags.contains(Flags::NO_EXEC) {
+        return false;
+    }
+    let page = Addr::new(addr & !(frame::FRAME_SIZE - 1));
+    // The fault may have interrupted a holder of the allocator lock, in which
+    // case it cannot be resolved here and is reported as unhandled.
+    let result = ALLOCATOR.try_with_stage1(|stage1| {
+        let translated = stage1.mapper().translate_entry(page);
+        match translated {
+            None => demand_zero(stage1, page, region.flags),
+            Some((entry, PageSize::Small)) => {
+                if !code.contains(ErrorCode::WRITE)
+                    || region.kind != RegionKind::CopyOnWrite
+                    || !entry.flags.contains(COW) {
+                    return Err(Error::Forbidden(addr));
+                }
+                copy_on_write(stage1, page, frame::Frame::new(entry.addr), region.flags)
+            },
+            Some(_) => Err(Error::HugePageConflict(addr))
+        }
+    });
+    result.is_ok()
+}
+
+fn demand_zero(stage1: &mut stage1::Allocator, page: Addr, flags: Flags)
+    -> Result<(), Error> {
+    let new_frame = match stage1.frame_allocator.alloc() {
+        Ok(frame) => frame,
+        Err(error) => return Err(Error::from(error))
+    };
+    if let Err(error) = stage1.mapper().map_to(page, new_frame.base.addr,
+        flags | Flags::WRITABLE | OWNED) {
+        stage1.frame_allocator.dealloc(new_frame);
+        return Err(error);
+    }
+    unsafe {
+        ptr::write_bytes(page.addr as *mut u8, 0, frame::FRAME_SIZE);
+    }
+    match flags.contains(Flags::WRITABLE) {
+        true => Ok(()),
+        false => stage1.mapper().protect(page, frame::FRAME_SIZE, flags)
+    }
+}
+
+fn copy_on_write(stage1: &mut stage1::Allocator,
+                 page: Addr,
+                 old_frame: frame::Frame,
+                 flags: Flags) -> Result<(), Error> {
+    if !stage1.frame_allocator.unshare(&old_frame) {
+        return stage1.mapper().protect(page, frame::FRAME_SIZE, flags);
+    }
+    let new_frame = match stage1.frame_allocator.alloc() {
+        Ok(frame) => frame,
+        Err(error) => {
+            stage1.frame_allocator.share(&old_frame);
+            return Err(Error::from(error));
+        }
+    };
+    if let Err(error) = address_space::copy_frame(stage1, old_frame.base.addr,
+        new_frame.base.addr) {
+        stage1.frame_allocator.share(&old_frame);
+        stage1.frame_allocator.dealloc(new_frame);
+        return Err(error);
+    }
+    let mut mapper = stage1.mapper();
+    if let Err(error) = mapper.unmap_range(page, frame::FRAME_SIZE) {
+        return Err(error);
+    }
+    mapper.map_to(page, new_frame.base.addr, flags | OWNED)
+}
@@ -1,12 +1,14 @@
 use crate::addr::Addr;
+use crate::area::Area;
 use crate::AllocError;
-use crate::stack::Stack;
-use crate::block::Block;
+use crate::bitmap::{Bitmap, MAX_MEMORY};
 
 use multiboot2;
 
 pub const FRAME_SIZE: usize = 4096;
 
+const BOOT_MAPPED: usize = 1 << 30;
+
 #[derive(Copy, Clone)]
 pub struct Frame {
     pub base: Addr
@@ -29,9 +31,10 @@ impl Frame {
 pub struct Allocator {
     kernel_start: usize,
     kernel_end: usize,
-    memory_size: usize,
-    free_base: Addr,
-    frame_stack: Stack,
+    metadata_start: usize,
+    bitmap: Bitmap,
+    shares: *mut u16,
+    hint: usize,
     pub mb2: multiboot2::Info
 }
 
@@ -39,56 +42,233 @@ impl Allocator {
     pub fn new(mb2: multiboot2::Info) -> Allocator {
         let kstart = mb2.get_elf_sections()
             .expect("No ELF section found in multiboot2 info")
+            .filter(|x| x.is_allocated())
             .map(|x| x.sh_addr)
             .min().unwrap();
         let kend = mb2.get_elf_sections()
             .expect("No ELF section found in multiboot2 info")
+            .filter(|x| x.is_allocated())
             .map(|x| x.sh_addr + x.sh_size)
             .max().unwrap();
-        let mem_size = mb2.get_basic_mem_info()
-            .expect("No basic memory information in multiboot2 info")
-            .mem_upper * 1024;
-        Allocator {
+        let mut top = 0;
+        for_each_region(&mb2, &mut |base, len| top = top.max(base + len));
+        if top > MAX_MEMORY {
+            log::warn!("Ignoring {} MiB of memory above {:#x}", (top - MAX_MEMORY) >> 20,
+                MAX_MEMORY);
+            top = MAX_MEMORY;
+        }
+        let frames = top / FRAME_SIZE;
+        let size = metadata_size(frames);
+        let metadata_start = place_metadata(&mb2, kstart, kend, size)
+            .expect("No room for the frame metadata in low memory");
+        let shares = (metadata_start + Bitmap::size(frames)) as *mut u16;
+        unsafe {
+            core::ptr::write_bytes(shares, 0, frames);
+        }
+        let mut allocator = Allocator {
             kernel_start: kstart as usize,
             kernel_end: kend as usize,
-            memory_size: mem_size as usize,
-            free_base: Addr::new(super::UPPER_MEMORY_BOUND),
-            frame_stack: Stack::new(),
+            metadata_start: metadata_start,
+            bitmap: unsafe { Bitmap::new(metadata_start, frames) },
+            shares: shares,
+            hint: 0,
             mb2: mb2
+        };
+        let mb2 = allocator.mb2;
+        for_each_region(&mb2, &mut |base, len| allocator.add_region(base, len));
+        allocator.reserve(allocator.kernel_start, allocator.kernel_end);
+        allocator.reserve(metadata_start, metadata_start + size);
+        for section in allocator.mb2.get_elf_sections().unwrap()
+            .filter(|x| x.is_symbolic()) {
+            allocator.reserve(section.sh_addr, section.sh_addr + section.sh_size);
+        }
+        allocator.reserve(allocator.mb2.base,
+            allocator.mb2.base + allocator.mb2.total_size as usize);
+        for module in allocator.mb2.get_modules() {
+            allocator.reserve(module.start as usize, module.end as usize);
+        }
+        allocator
+    }
+
+    fn add_region(&mut self, base: usize, len: usize) {
+        let mut start = (base + FRAME_SIZE - 1) / FRAME_SIZE;
+        let mut end = (base + len) / FRAME_SIZE;
+        if start < super::UPPER_MEMORY_BOUND / FRAME_SIZE {
+            start = super::UPPER_MEMORY_BOUND / FRAME_SIZE;
+        }
+        if end > self.bitmap.frames() {
+            end = self.bitmap.frames();
+        }
+        if start < end {
+            self.bitmap.set_free(start, end - start);
+        }
+    }
+
+    fn reserve(&mut self, start: usize, end: usize) {
+        let first = start / FRAME_SIZE;
+        let mut last = (end + FRAME_SIZE - 1) / FRAME_SIZE;
+        if last > self.bitmap.frames() {
+            last = self.bitmap.frames();
+        }
+        if first < last {
+            self.bitmap.set_used(first, last - first);
+        }
+    }
+
+    pub fn frames(&self) -> usize {
+        self.bitmap.frames()
+    }
+
+    pub fn metadata_area(&self) -> Area {
+        Area::new(self.metadata_start, metadata_size(self.bitmap.frames()))
+    }
+
+    pub(crate) fn share(&mut self, frame: &Frame) -> bool {
+        let index = frame.base.addr / FRAME_SIZE;
+        if index >= self.bitmap.frames() {
+            return false;
+        }
+        let count = unsafe { &mut *self.shares.add(index) };
+        match count.checked_add(1) {
+            Some(shares) => {
+                *count = shares;
+                true
+            },
+            None => false
+        }
+    }
+
+    pub(crate) fn unshare(&mut self, frame: &Frame) -> bool {
+        let index = frame.base.addr / FRAME_SIZE;
+        if index >= self.bitmap.frames() {
+            return false;
+        }
+        let count = unsafe { &mut *self.shares.add(index) };
+        match count.checked_sub(1) {
+            Some(shares) => {
+                *count = shares;
+                true
+            },
+            None => false
         }
     }
 
     pub fn inspect(&self) {
-        vga::println!("{}", self.free_base.addr);
-        self.frame_stack.inspect();
+        log::info!("{} free frames", self.bitmap.free);
     }
 
     pub fn alloc(&mut self) -> Result<Frame, AllocError> {
-        match self.frame_stack.pop() {
-            Some(frame) => Ok(frame),
-            None => {
-                loop {
-                    let frame = Frame::new(self.free_base.addr);
-                    self.free_base.addr += FRAME_SIZE;
-                    if frame.base.addr >= self.kernel_start && frame.base.addr <= self.kernel_end {
-                        continue;
-                    } else if frame.base.addr < super::UPPER_MEMORY_BOUND {
-                        continue;
-                    } else if frame.base.addr > self.memory_size {
-                        return Err(AllocError::OutOfMemory);
-                    } else {
-                        return Ok(frame);
-                    }
-                }
+        let index = match self.bitmap.find(1, 1, self.hint) {
+            Some(index) => index,
+            None => match self.bitmap.find(1, 1, 0) {
+                Some(index) => index,
+                None => return Err(AllocError::OutOfMemory)
             }
+        };
+        self.bitmap.set_used(index, 1);
+        self.hint = index + 1;
+        Ok(Frame::new(index * FRAME_SIZE))
+    }
+
+    pub fn alloc_contiguous(&mut self, count: usize, align: usize) -> Result<Frame, AllocError> {
+        if count == 0 || !align.is_power_of_two() {
+            return Err(AllocError::InvalidLayout);
+        }
+        let align_frames = match align / FRAME_SIZE {
+            0 => 1,
+            frames => frames
+        };
+        match self.bitmap.find(count, align_frames, 0) {
+            Some(index) => {
+                self.bitmap.set_used(index, count);
+                Ok(Frame::new(index * FRAME_SIZE))
+            },
+            None => Err(AllocError::OutOfMemory)
+        }
+    }
+
+    pub fn dealloc(&mut self, frame: Frame) {
+        self.dealloc_range(frame, 1);
+    }
+
+    pub fn dealloc_range(&mut self, frame: Frame, count: usize) {
+        let index = frame.base.addr / FRAME_SIZE;
+        if index + count > self.bitmap.frames() {
+            panic!("Freeing frames outside of physical memory: {:x}", frame.base.addr);
+        }
+        for i in index..index + count {
+            if self.bitmap.is_free(i) {
+                panic!("Double free of frame {:x}", i * FRAME_SIZE);
+            }
+        }
+        self.bitmap.set_free(index, count);
+        if index < self.hint {
+            self.hint = index;
         }
     }
+}
 
-    pub fn dealloc(&mut self, frame: Frame) -> bool {
-        self.frame_stack.push(frame)
+fn for_each_region(mb2: &multiboot2::Info, f: &mut dyn FnMut(usize, usize)) {
+    match mb2.get_mem_map() {
+        Some(mem_map) => {
+            for entry in mem_map.entries().filter(|x| x.is_available()) {
+                f(entry.base_addr as usize, entry.length as usize);
+            }
+        },
+        None => {
+            let mem_upper = mb2.get_basic_mem_info()
+                .expect("No memory information in multiboot2 info")
+                .mem_upper as usize * 1024;
+            f(super::UPPER_MEMORY_BOUND, mem_upper);
+        }
     }
+}
 
-    pub fn pool(&mut self, block: &Block) {
-        self.frame_stack.pool(block);
+fn reserved_end(mb2: &multiboot2::Info, kstart: usize, kend: usize, start: usize, end: usize)
+    -> Option<usize> {
+    let overlaps = |base: usize, limit: usize| {
+        let base = base & !(FRAME_SIZE - 1);
+        let limit = (limit + FRAME_SIZE - 1) & !(FRAME_SIZE - 1);
+        match base < end && start < limit {
+            true => Some(limit),
+            false => None
+        }
+    };
+    if let Some(limit) = overlaps(kstart, kend) {
+        return Some(limit);
+    }
+    if let Some(limit) = overlaps(mb2.base, mb2.base + mb2.total_size as usize) {
+        return Some(limit);
+    }
+    if let Some(limit) = mb2.get_modules()
+        .filter_map(|x| overlaps(x.start as usize, x.end as usize))
+        .max() {
+        return Some(limit);
     }
+    mb2.get_elf_sections().unwrap()
+        .filter(|x| x.is_symbolic())
+        .filter_map(|x| overlaps(x.sh_addr, x.sh_addr + x.sh_size))
+        .max()
+}
+
+fn metadata_size(frames: usize) -> usize {
+    let size = Bitmap::size(frames) + frames * core::mem::size_of::<u16>();
+    (size + FRAME_SIZE - 1) & !(FRAME_SIZE - 1)
+}
+
+fn place_metadata(mb2: &multiboot2::Info, kstart: usize, kend: usize, size: usize)
+    -> Option<usize> {
+    let mut found = None;
+    for_each_region(mb2, &mut |base, len| {
+        let limit = (base + len).min(BOOT_MAPPED);
+        let mut start = (base.max(super::UPPER_MEMORY_BOUND) + FRAME_SIZE - 1)
+            & !(FRAME_SIZE - 1);
+        while found.is_none() && start + size <= limit {
+            match reserved_end(mb2, kstart, kend, start, start + size) {
+                Some(end) => start = end,
+                None => found = Some(start)
+            }
+        }
+    });
+    found
 }
@@ -0,0 +1,148 @@
+use crate::addr::Addr;
+use crate::allocator::ALLOCATOR;
+use crate::frame;
+use crate::paging::{Error, Flags};
+use crate::stage1;
+
+use spinlock::Mutex;
+
+pub const STACK_SLOT: usize = 506;
+pub const STRIDE: usize = 1 << 20;
+pub const MAX_PAGES: usize = STRIDE / frame::FRAME_SIZE - 1;
+pub const DEFAULT_PAGES: usize = 16;
+
+const MAX_STACKS: usize = 1024;
+const WORDS: usize = MAX_STACKS / 64;
+
+static SLOTS: Mutex<[u64; WORDS]> = Mutex::new([0; WORDS]);
+
+#[derive(Debug)]
+pub struct Stack {
+    index: usize,
+    pages: usize
+}
+
+impl Stack {
+    pub fn guard(&self) -> usize {
+        slot_addr(self.index)
+    }
+
+    pub fn bottom(&self) -> usize {
+        self.guard() + frame::FRAME_SIZE
+    }
+
+    pub fn top(&self) -> usize {
+        self.bottom() + self.pages * frame::FRAME_SIZE
+    }
+
+    pub fn size(&self) -> usize {
+        self.pages * frame::FRAME_SIZE
+    }
+}
+
+fn base() -> usize {
+    let mut addr = Addr::new(STACK_SLOT << 39);
+    addr.to_valid();
+    addr.addr
+}
+
+fn slot_addr(index: usize) -> usize {
+    base() + index * STRIDE
+}
+
+fn reserve_slot() -> Option<usize> {
+    let mut slots = SLOTS.lock();
+    for (i, word) in slots.iter_mut().enumerate() {
+        if *word != !0 {
+            let bit = (!*word).trailing_zeros() as usize;
+            *word |= 1 << bit;
+            return Some(i * 64 + bit);
+        }
+    }
+    None
+}
+
+fn release_slot(index: usize) {
+    SLOTS.lock()[index / 64] &= !(1 << (index % 64));
+}
+
+pub fn alloc(pages: usize) -> Result<Stack, Error> {
+    if pages == 0 || pages > MAX_PAGES {
+        return Err(Error::InvalidSize(pages * frame::FRAME_SIZE));
+    }
+    let stack = match reserve_slot() {
+        Some(index) => Stack {
+            index: index,
+            pages: pages
+        },
+        None => return Err(Error::OutOfMemory)
+    };
+    let result = ALLOCATOR.with_stage1(|stage1| {
+        for page in 0..pages {
+            let addr = stack.bottom() + page * frame::FRAME_SIZE;
+            if let Err(error) = map_page(stage1, addr) {
+                release_pages(stage1, stack.bottom(), page);
+                return Err(error);
+            }
+        }
+        Ok(())
+    });
+    match result {
+        Ok(()) => Ok(stack),
+        Err(error) => {
+            release_slot(stack.index);
+            Err(error)
+        }
+    }
+}
+
+pub fn dealloc(stack: Stack) {
+    let result = ALLOCATOR.with_stage1(|stage1| {
+        release_pages(stage1, stack.bottom(), stack.pages);
+        Ok(())
+    });
+    if let Err(error) = result {
+        panic!("Unable to free kernel stack {:?}: {:?}", stack, error);
+    }
+    release_slot(stack.index);
+}
+
+pub fn is_guard(addr: usize) -> bool {
+    if addr < base() || addr >= slot_addr(MAX_STACKS) {
+        return false;
+    }
+    let index = (addr - base()) / STRIDE;
+    // Called from the fault path: if the slots are being updated, trust the
+    // address alone rather than waiting on the lock.
+    let reserved = match SLOTS.try_lock() {
+        Some(slots) => slots[index / 64] & (1 << (index % 64)) != 0,
+        None => true
+    };
+    reserved && addr - slot_addr(index) < frame::FRAME_SIZE
+}
+
+fn map_page(stage1: &mut stage1::Allocator, addr: usize) -> Result<(), Error> {
+    let page = match stage1.frame_allocator.alloc() {
+        Ok(frame) => frame,
+        Err(error) => return Err(Error::from(error))
+    };
+    let flags = Flags::PRESENT | Flags::WRITABLE | Flags::NO_EXEC;
+    if let Err(error) = stage1.kernel_mapper().map_to(Addr::new(addr), page.base.addr, flags) {
+        stage1.frame_allocator.dealloc(page);
+        return Err(error);
+    }
+    Ok(())
+}
+
+fn release_pages(stage1: &mut stage1::Allocator, bottom: usize, pages: usize) {
+    for page in 0..pages {
+        let addr = Addr::new(bottom + page * frame::FRAME_SIZE);
+        let phys = match stage1.kernel_mapper().translate(addr) {
+            Some(phys) => phys,
+            None => continue
+        };
+        if stage1.kernel_mapper().unmap_range(addr, frame::FRAME_SIZE).is_ok() {
+            stage1.frame_allocator.dealloc(frame::Frame::new(phys));
+        }
+    }
+}
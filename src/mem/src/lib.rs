@@ -1,6 +1,8 @@
 #![no_std]
 #![feature(asm)]
 
+extern crate alloc;
+
 const UPPER_MEMORY_BOUND: usize = 1 << 20;
 
 mod frame;
@@ -11,11 +13,17 @@ mod stage2;
 mod memtree;
 pub mod block;
 mod slab;
-mod stack;
+mod bitmap;
+mod tlb;
 
 pub mod addr;
 pub mod area;
 pub mod allocator;
+pub mod paging;
+pub mod address_space;
+pub mod fault;
+pub mod kstack;
+pub mod mmio;
 
 #[derive(Debug)]
 pub enum AllocError {
@@ -25,4 +33,5 @@ pub enum AllocError {
     InvalidAddr,
     Forbidden,
     InvalidInit,
+    InvalidLayout,
 }
@@ -436,7 +436,7 @@ impl<'a> NodeType<'a> {
     pub unsafe fn inspect(&self, depth: usize) {
         if self.is_node() {
             self.left().inspect(depth + 1);
-            vga::println!("{}: {:?} {:?}", depth, self.get_color(), self.content());
+            log::debug!("{}: {:?} {:?}", depth, self.get_color(), self.content());
             self.right().inspect(depth + 1);
         }
     }
@@ -536,7 +536,7 @@ impl<'a, 'b> Tree<'a> {
     pub fn inspect(&self) {
         unsafe {
             if !self.root.is_node() {
-                vga::println!("Empty!");
+                log::debug!("Empty!");
             } else {
                 self.root.inspect(0);
             }
@@ -0,0 +1,51 @@
+use crate::addr::Addr;
+use crate::allocator::ALLOCATOR;
+use crate::frame::FRAME_SIZE;
+use crate::paging::{Error, Flags};
+
+use spinlock::Mutex;
+
+pub const MMIO_SLOT: usize = 505;
+
+const WINDOW_SIZE: usize = 1 << 39;
+
+static NEXT: Mutex<usize> = Mutex::new(0);
+
+fn base() -> usize {
+    let mut addr = Addr::new(MMIO_SLOT << 39);
+    addr.to_valid();
+    addr.addr
+}
+
+pub fn map(phys: usize, len: usize) -> Result<usize, Error> {
+    map_with(phys, len, Flags::PRESENT
+        | Flags::WRITABLE
+        | Flags::WRITE_THROUGH
+        | Flags::CACHE_DISABLE
+        | Flags::NO_EXEC)
+}
+
+// For firmware data living in RAM, such as ACPI tables: read-only, cacheable.
+pub fn map_memory(phys: usize, len: usize) -> Result<usize, Error> {
+    map_with(phys, len, Flags::PRESENT | Flags::NO_EXEC)
+}
+
+fn map_with(phys: usize, len: usize, flags: Flags) -> Result<usize, Error> {
+    let offset = phys & (FRAME_SIZE - 1);
+    let size = (len + offset + FRAME_SIZE - 1) & !(FRAME_SIZE - 1);
+    if len == 0 {
+        return Err(Error::InvalidSize(len));
+    }
+    let mut next = NEXT.lock();
+    if *next + size > WINDOW_SIZE {
+        return Err(Error::OutOfMemory);
+    }
+    let virt = base() + *next;
+    if let Err(error) = ALLOCATOR.with_stage1(|stage1| {
+        stage1.kernel_mapper().map_range(Addr::new(virt), phys - offset, size, flags)
+    }) {
+        return Err(error);
+    }
+    *next += size;
+    Ok(virt + offset)
+}
@@ -0,0 +1,228 @@
+use crate::addr::Addr;
+use crate::address_space::{USER_END, USER_START};
+use crate::allocator::ALLOCATOR;
+use crate::entry::Entry;
+use crate::frame;
+use crate::table::{TableLevel, PML4};
+use crate::tlb::Flush;
+use crate::AllocError;
+
+pub use crate::entry::Flags;
+pub use crate::table::PageSize;
+
+const RESERVED_START: usize = 0o177777_776_000_000_000_0000;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Error {
+    Uninitialized,
+    OutOfMemory,
+    InvalidSize(usize),
+    Unaligned(usize),
+    NonCanonical(usize),
+    Forbidden(usize),
+    AlreadyMapped(usize),
+    NotMapped(usize),
+    HugePageConflict(usize),
+    SizeMismatch(usize),
+    ActiveAddressSpace,
+    Busy
+}
+
+impl From<AllocError> for Error {
+    fn from(error: AllocError) -> Error {
+        match error {
+            AllocError::Uninitialized => Error::Uninitialized,
+            _ => Error::OutOfMemory
+        }
+    }
+}
+
+impl From<Error> for AllocError {
+    fn from(error: Error) -> AllocError {
+        match error {
+            Error::Uninitialized => AllocError::Uninitialized,
+            Error::OutOfMemory => AllocError::OutOfMemory,
+            Error::InvalidSize(_) => AllocError::InvalidLayout,
+            Error::AlreadyMapped(_) | Error::HugePageConflict(_) => AllocError::InUse,
+            Error::Forbidden(_) => AllocError::Forbidden,
+            Error::ActiveAddressSpace | Error::Busy => AllocError::InUse,
+            Error::Unaligned(_)
+                | Error::NonCanonical(_)
+                | Error::NotMapped(_)
+                | Error::SizeMismatch(_) => AllocError::InvalidAddr
+        }
+    }
+}
+
+pub struct Mapper<'m> {
+    pml4: &'m mut PML4,
+    frame_allocator: &'m mut frame::Allocator,
+    huge_pages: bool,
+    start: usize,
+    limit: usize
+}
+
+impl<'m> Mapper<'m> {
+    pub(crate) fn new(pml4: &'m mut PML4,
+                      frame_allocator: &'m mut frame::Allocator,
+                      huge_pages: bool) -> Mapper<'m> {
+        Mapper {
+            pml4: pml4,
+            frame_allocator: frame_allocator,
+            huge_pages: huge_pages,
+            start: USER_START,
+            limit: USER_END
+        }
+    }
+
+    pub(crate) fn kernel(mut self) -> Mapper<'m> {
+        self.start = 0;
+        self.limit = RESERVED_START;
+        self
+    }
+
+    pub fn map_to(&mut self, virt: Addr, phys: usize, flags: Flags) -> Result<(), Error> {
+        self.map_range(virt, phys, frame::FRAME_SIZE, flags)
+    }
+
+    pub fn map_range(&mut self, virt: Addr, phys: usize, len: usize, flags: Flags)
+        -> Result<(), Error> {
+        if let Err(error) = check_range(&virt, len, self.start, self.limit) {
+            return Err(error);
+        }
+        if phys & (frame::FRAME_SIZE - 1) != 0 {
+            return Err(Error::Unaligned(phys));
+        }
+        let mut flush = Flush::new();
+        let mut offset = 0;
+        while offset < len {
+            let page = Addr::new(virt.addr + offset);
+            let size = PageSize::fitting(page.addr | (phys + offset), len - offset,
+                self.huge_pages);
+            if let Err(error) = self.pml4.map_page(&page, Entry::new(phys + offset, flags),
+                size, self.frame_allocator, &mut flush) {
+                if offset > 0 {
+                    let _ = self.unmap_range(virt, offset);
+                }
+                return Err(error);
+            }
+            offset += size.size();
+        }
+        Ok(())
+    }
+
+    pub fn unmap_range(&mut self, virt: Addr, len: usize) -> Result<(), Error> {
+        if let Err(error) = check_range(&virt, len, self.start, self.limit) {
+            return Err(error);
+        }
+        let mut flush = Flush::new();
+        let mut offset = 0;
+        while offset < len {
+            let page = Addr::new(virt.addr + offset);
+            let size = match self.page_size(&page, len - offset) {
+                Some(size) => size,
+                None => return Err(Error::NotMapped(page.addr))
+            };
+            if let Err(error) = self.pml4.unmap_page(&page, size, self.frame_allocator,
+                &mut flush) {
+                return Err(error);
+            }
+            offset += size.size();
+        }
+        Ok(())
+    }
+
+    pub fn protect(&mut self, virt: Addr, len: usize, flags: Flags) -> Result<(), Error> {
+        if let Err(error) = check_range(&virt, len, self.start, self.limit) {
+            return Err(error);
+        }
+        let mut flush = Flush::new();
+        let mut offset = 0;
+        while offset < len {
+            let page = Addr::new(virt.addr + offset);
+            let size = match self.page_size(&page, len - offset) {
+                Some(size) => size,
+                None => return Err(Error::NotMapped(page.addr))
+            };
+            if let Err(error) = self.pml4.protect_page(&page, flags, size,
+                self.frame_allocator, &mut flush) {
+                return Err(error);
+            }
+            offset += size.size();
+        }
+        Ok(())
+    }
+
+    pub fn translate(&self, virt: Addr) -> Option<usize> {
+        if !virt.is_valid() {
+            return None;
+        }
+        match self.pml4.translate(&virt) {
+            Some((entry, size)) => Some(entry.addr + (virt.addr & (size.size() - 1))),
+            None => None
+        }
+    }
+
+    pub fn translate_entry(&self, virt: Addr) -> Option<(Entry, PageSize)> {
+        self.pml4.translate(&virt)
+    }
+
+    fn page_size(&self, page: &Addr, remaining: usize) -> Option<PageSize> {
+        match self.pml4.translate(page) {
+            Some((_, size)) => {
+                if page.addr & (size.size() - 1) == 0 && remaining >= size.size() {
+                    Some(size)
+                } else {
+                    Some(PageSize::Small)
+                }
+            },
+            None => None
+        }
+    }
+}
+
+fn check_range(virt: &Addr, len: usize, start: usize, limit: usize) -> Result<(), Error> {
+    if !virt.is_valid() {
+        return Err(Error::NonCanonical(virt.addr));
+    }
+    if virt.addr & (frame::FRAME_SIZE - 1) != 0 {
+        return Err(Error::Unaligned(virt.addr));
+    }
+    if len == 0 || len & (frame::FRAME_SIZE - 1) != 0 {
+        return Err(Error::InvalidSize(len));
+    }
+    let end = match virt.addr.checked_add(len - 1) {
+        Some(end) => Addr::new(end),
+        None => return Err(Error::InvalidSize(len))
+    };
+    if !end.is_valid() {
+        return Err(Error::NonCanonical(end.addr));
+    }
+    if virt.addr < start || end.addr >= limit {
+        return Err(Error::Forbidden(virt.addr));
+    }
+    Ok(())
+}
+
+pub fn map_to(virt: Addr, phys: usize, flags: Flags) -> Result<(), Error> {
+    ALLOCATOR.mapper(|mapper| mapper.map_to(virt, phys, flags))
+}
+
+pub fn map_range(virt: Addr, phys: usize, len: usize, flags: Flags) -> Result<(), Error> {
+    ALLOCATOR.mapper(|mapper| mapper.map_range(virt, phys, len, flags))
+}
+
+pub fn unmap_range(virt: Addr, len: usize) -> Result<(), Error> {
+    ALLOCATOR.mapper(|mapper| mapper.unmap_range(virt, len))
+}
+
+pub fn protect(virt: Addr, len: usize, flags: Flags) -> Result<(), Error> {
+    ALLOCATOR.mapper(|mapper| mapper.protect(virt, len, flags))
+}
+
+pub fn translate(virt: Addr) -> Option<usize> {
+    match ALLOCATOR.mapper(|mapper| Ok(mapper.translate(virt))) {
+        Ok(phys) => phys,
+        Err(_) => None
+    }
+}
@@ -1,19 +1,20 @@
 use crate::addr::Addr;
 use crate::allocator::PML4_ADDR;
 use crate::area::Area;
-use crate::entry;
-use crate::entry::Entry;
+use crate::entry::{Entry, Flags};
 use crate::frame;
-use crate::table::{TableLevel, PML4};
+use crate::table::{PageSize, TableLevel, PML4};
 use crate::AllocError;
+use crate::paging::{self, Mapper};
 use crate::UPPER_MEMORY_BOUND;
 use crate::block::Block;
-
-const NEW_PML4: Addr = Addr::new(0xdeadbeef000);
+use crate::tlb::Flush;
+use crate::address_space::{self, RECURSIVE_SLOT};
 
 pub struct Allocator {
     pub frame_allocator: frame::Allocator,
-    pml4: PML4,
+    pub(crate) pml4: PML4,
+    pub(crate) huge_pages: bool,
 }
 
 impl Allocator {
@@ -21,6 +22,7 @@ impl Allocator {
         let mut allocator = Allocator {
             frame_allocator: frame::Allocator::new(mb2),
             pml4: PML4::new(&PML4_ADDR, 511),
+            huge_pages: asm::x86_64::cpuid::has_1g_pages(),
         };
         let (new_pml4, pml4_frame) = match allocator.create_new_pml4() {
             Ok(res) => res,
@@ -39,28 +41,42 @@ impl Allocator {
         unsafe {
             asm::x86_64::reg::tlb::update(pml4_frame.base.addr);
         }
+        address_space::set_kernel_root(&pml4_frame);
         allocator.pml4 = new_pml4;
+        if let Err(error) = allocator.share_kernel_slots() {
+            panic!("Unable to allocate kernel page tables: {:?}", error);
+        }
         allocator
     }
 
-    pub fn unmap(&mut self, addr: &Addr) -> Result<frame::Frame, AllocError> {
-        self.pml4.unmap_frame(addr)
+    pub fn mapper(&mut self) -> Mapper {
+        Mapper::new(&mut self.pml4, &mut self.frame_allocator, self.huge_pages)
+    }
+
+    pub(crate) fn kernel_mapper(&mut self) -> Mapper {
+        self.mapper().kernel()
     }
 
-    pub fn map(&mut self, block: &Block) -> Result<(), AllocError> {
+    pub fn unmap(&mut self, addr: &Addr, flush: &mut Flush) -> Result<frame::Frame, AllocError> {
+        match self.pml4.unmap_frame(addr, &mut self.frame_allocator, flush) {
+            Ok(frame) => Ok(frame),
+            Err(error) => Err(AllocError::from(error))
+        }
+    }
+
+    pub fn map(&mut self, block: &Block, flags: Flags) -> Result<(), AllocError> {
         let area = Area::new(block.addr, block.size());
+        let mut flush = Flush::new();
         for page in area.pages() {
             match self.frame_allocator.alloc() {
                 Ok(frame) => {
                     if let Err(error) = self.pml4.map_frame(
                         &page,
-                        entry::Entry::new(
-                            frame.base.addr,
-                            entry::FLAG_PRESENT | entry::FLAG_WRITABLE,
-                        ),
+                        Entry::new(frame.base.addr, flags),
                         &mut self.frame_allocator,
+                        &mut flush,
                     ) {
-                        return Err(error);
+                        return Err(AllocError::from(error));
                     }
                 }
                 Err(error) => return Err(error),
@@ -69,76 +85,106 @@ impl Allocator {
         Ok(())
     }
 
-    fn create_new_pml4(&mut self) -> Result<(PML4, frame::Frame), AllocError> {
+    fn create_new_pml4(&mut self) -> Result<(PML4, frame::Frame), paging::Error> {
         let pml4_frame = match self.frame_allocator.alloc() {
             Ok(frame) => frame,
-            Err(error) => return Err(error),
+            Err(error) => return Err(paging::Error::from(error)),
         };
-        if let Err(error) = self.pml4.map_frame(
-            &NEW_PML4,
-            Entry::new(
-                pml4_frame.base.addr,
-                entry::FLAG_WRITABLE | entry::FLAG_PRESENT,
-            ),
-            &mut self.frame_allocator,
-        ) {
-            return Err(error);
+        let entry = Entry::new(pml4_frame.base.addr, Flags::WRITABLE | Flags::PRESENT);
+        self.pml4.set_entry(RECURSIVE_SLOT, Entry::from_entry(entry.value()));
+        unsafe {
+            asm::x86_64::reg::tlb::flush();
         }
-        let mut new_pml4 = PML4::new(&NEW_PML4, 510);
+        let mut new_pml4 = PML4::new(&self.pml4.child_addr(RECURSIVE_SLOT), RECURSIVE_SLOT);
         new_pml4.flush(0, 511);
-        new_pml4.set_entry(
-            510,
-            Entry::new(
-                pml4_frame.base.addr,
-                entry::FLAG_WRITABLE | entry::FLAG_PRESENT,
-            ),
-        );
-        self.pml4.set_entry(
-            510,
-            Entry::new(
-                pml4_frame.base.addr,
-                entry::FLAG_WRITABLE | entry::FLAG_PRESENT,
-            ),
-        );
-        if let Err(error) = new_pml4.map_frame(
-            &NEW_PML4,
-            Entry::new(
-                pml4_frame.base.addr,
-                entry::FLAG_WRITABLE | entry::FLAG_PRESENT,
-            ),
-            &mut self.frame_allocator,
-        ) {
-            return Err(error);
+        new_pml4.set_entry(RECURSIVE_SLOT, entry);
+        unsafe {
+            asm::x86_64::reg::tlb::flush();
+        }
+        Ok((PML4::new(&address_space::pml4_addr(RECURSIVE_SLOT), RECURSIVE_SLOT), pml4_frame))
+    }
+
+    fn share_kernel_slots(&mut self) -> Result<(), paging::Error> {
+        for slot in address_space::KERNEL_SLOTS_START..address_space::TARGET_SLOT {
+            if let Err(error) = self.pml4.allocate_entry(slot, Flags::NONE,
+                &mut self.frame_allocator) {
+                return Err(error);
+            }
         }
-        Ok((new_pml4, pml4_frame))
+        Ok(())
     }
 
-    fn remap_kernel(&mut self, mut new_pml4: PML4) -> Result<(), AllocError> {
-        for section in self.frame_allocator.mb2.get_elf_sections().unwrap() {
+    fn remap_kernel(&mut self, mut new_pml4: PML4) -> Result<(), paging::Error> {
+        for section in self.frame_allocator.mb2.get_elf_sections().unwrap()
+            .filter(|section| section.is_allocated()) {
             let area = Area::new(section.sh_addr, section.sh_size);
-            for addr in area.pages() {
-                if let Err(error) = new_pml4.map_frame(
-                    &addr,
-                    Entry::from_elf(addr.addr, section.sh_flags),
-                    &mut self.frame_allocator,
-                ) {
-                    return Err(error);
-                }
+            let flags = Entry::from_elf(section.sh_addr, section.sh_flags).flags;
+            if let Err(error) = self.identity_map(&mut new_pml4, &area, flags) {
+                return Err(error);
+            }
+        }
+        let metadata = self.frame_allocator.metadata_area();
+        if let Err(error) = self.identity_map(&mut new_pml4, &metadata,
+            Flags::PRESENT | Flags::WRITABLE | Flags::NO_EXEC) {
+            return Err(error);
+        }
+        for section in self.frame_allocator.mb2.get_elf_sections().unwrap()
+            .filter(|section| section.is_symbolic()) {
+            let area = Area::new(section.sh_addr, section.sh_size);
+            if let Err(error) = self.identity_map_unmapped(&mut new_pml4, &area,
+                Flags::PRESENT | Flags::NO_EXEC) {
+                return Err(error);
             }
         }
         Ok(())
     }
 
-    fn remap_low_memory(&mut self, mut new_pml4: PML4) -> Result<(), AllocError> {
+    fn remap_low_memory(&mut self, mut new_pml4: PML4) -> Result<(), paging::Error> {
         let area = Area::new(0, UPPER_MEMORY_BOUND);
-        for addr in area.pages() {
-            if let Err(error) = new_pml4.map_frame(
-                &addr,
-                Entry::new(addr.addr, entry::FLAG_PRESENT | entry::FLAG_WRITABLE),
+        self.identity_map(&mut new_pml4, &area, Flags::PRESENT | Flags::WRITABLE)
+    }
+
+    fn identity_map(&mut self, new_pml4: &mut PML4, area: &Area, flags: Flags)
+        -> Result<(), paging::Error> {
+        let mut addr = area.base.addr & !(frame::FRAME_SIZE - 1);
+        let end = area.base.addr + area.len;
+        let mut flush = Flush::new();
+        while addr < end {
+            let size = PageSize::fitting(addr, end - addr, self.huge_pages);
+            if let Err(error) = new_pml4.map_page(
+                &Addr::new(addr),
+                Entry::new(addr, flags),
+                size,
                 &mut self.frame_allocator,
+                &mut flush,
             ) {
                 return Err(error);
             }
+            addr += size.size();
+        }
+        Ok(())
+    }
+
+    // Symbolic sections are packed together and may share pages with each
+    // other or with allocated sections, so only map the pages still missing.
+    fn identity_map_unmapped(&mut self, new_pml4: &mut PML4, area: &Area, flags: Flags)
+        -> Result<(), paging::Error> {
+        let mut addr = area.base.addr & !(frame::FRAME_SIZE - 1);
+        let end = area.base.addr + area.len;
+        let mut flush = Flush::new();
+        while addr < end {
+            if new_pml4.translate(&Addr::new(addr)).is_none() {
+                if let Err(error) = new_pml4.map_page(
+                    &Addr::new(addr),
+                    Entry::new(addr, flags),
+                    PageSize::Small,
+                    &mut self.frame_allocator,
+                    &mut flush,
+                ) {
+                    return Err(error);
+                }
+            }
+            addr += frame::FRAME_SIZE;
         }
         Ok(())
     }
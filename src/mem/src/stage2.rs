@@ -4,10 +4,14 @@ use crate::area::Area;
 use crate::block::Block;
 use crate::memtree;
 use crate::slab::Slab;
+use crate::entry::Flags;
+use crate::tlb::Flush;
 
 use core::alloc::Layout;
 
 const BUCKETS: usize = 49;
+const HEAP_START: usize = 1 << 47;
+const HEAP_ORDER: usize = 46;
 
 pub struct Allocator<'a> {
     internal: stage1::Allocator,
@@ -24,7 +28,7 @@ impl<'a> Allocator<'a> {
             blocks: memtree::Tree::new(),
             node_slab: Slab::new()
         };
-        allocator.buddies[BUCKETS - 1].insert_block(&Block::new(0, BUCKETS - 1));
+        allocator.buddies[HEAP_ORDER].insert_block(&Block::new(HEAP_START, HEAP_ORDER));
         unsafe {
             if let Ok(slab_block) = allocator.alloc_iter(21, 1 << 21) {
                 allocator.node_slab.init(&slab_block);
@@ -35,6 +39,10 @@ impl<'a> Allocator<'a> {
         allocator
     }
 
+    pub fn stage1(&mut self) -> &mut stage1::Allocator {
+        &mut self.internal
+    }
+
     pub fn inspect(&self) {
         self.internal.frame_allocator.inspect();
         self.blocks.inspect();
@@ -62,17 +70,10 @@ impl<'a> Allocator<'a> {
     fn dealloc_frame(&mut self, mut block: Block) {
         block.add_sign();
         let area = Area::new(block.addr, block.size());
+        let mut flush = Flush::new();
         for addr in area.pages() {
-            match self.internal.unmap(&addr) {
-                Ok(frame) => {
-                    if let false = self.internal.frame_allocator.dealloc(frame) {
-                        if let Ok(frame_block) = self.alloc_iter(12, 1 << 12) {
-                            self.internal.frame_allocator.pool(&frame_block);
-                        } else {
-                            panic!("Cannot pool frame nodes");
-                        }
-                    }
-                },
+            match self.internal.unmap(&addr, &mut flush) {
+                Ok(frame) => self.internal.frame_allocator.dealloc(frame),
                 Err(error) => panic!("Invalid unmap {} {} {} {:?}",
                     block.addr, block.size(), addr.addr, error)
             }
@@ -156,7 +157,7 @@ impl<'a> Allocator<'a> {
         while let Some(mut block) = self.choose_block(order, align) {
             while block.order > target {
                 if block.should_map(block.order, target) {
-                    match self.internal.map(&block) {
+                    match self.internal.map(&block, Flags::PRESENT | Flags::WRITABLE) {
                         Err(AllocError::InUse) => break,
                         Err(error) => return Err(error),
                         Ok(_) => {}
@@ -178,7 +179,7 @@ impl<'a> Allocator<'a> {
             }
             if block.order == target {
                 if block.should_map(order, target) {
-                    match self.internal.map(&block) {
+                    match self.internal.map(&block, Flags::PRESENT | Flags::WRITABLE) {
                         Err(AllocError::InUse) => {
                             order = block.order;
                             continue
@@ -1,19 +1,108 @@
 use crate::addr::Addr;
-use crate::entry::Entry;
-use crate::entry;
+use crate::entry::{Entry, Flags};
 use crate::frame;
-use crate::AllocError;
+use crate::paging::Error;
+use crate::tlb::Flush;
+use crate::address_space;
+use crate::fault;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum PageSize {
+    Small,
+    Large,
+    Huge
+}
+
+impl PageSize {
+    pub fn from_level(level: usize) -> PageSize {
+        match level {
+            1 => PageSize::Small,
+            2 => PageSize::Large,
+            _ => PageSize::Huge
+        }
+    }
+
+    pub fn level(&self) -> usize {
+        match *self {
+            PageSize::Small => 1,
+            PageSize::Large => 2,
+            PageSize::Huge => 3
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        1 << (12 + 9 * (self.level() - 1))
+    }
+
+    pub fn fitting(addr: usize, len: usize, huge_pages: bool) -> PageSize {
+        let candidates = [PageSize::Huge, PageSize::Large];
+        for size in candidates.iter() {
+            if *size == PageSize::Huge && !huge_pages {
+                continue;
+            }
+            if addr & (size.size() - 1) == 0 && len >= size.size() {
+                return *size;
+            }
+        }
+        PageSize::Small
+    }
+}
 
 pub trait TableLevel {
     type DownLevel: TableLevel;
 
+    fn map_page(&mut self,
+                addr: &Addr,
+                entry: Entry,
+                size: PageSize,
+                frame_allocator: &mut frame::Allocator,
+                flush: &mut Flush)
+        -> Result<(), Error>;
+
+    fn unmap_page(&mut self,
+                  addr: &Addr,
+                  size: PageSize,
+                  frame_allocator: &mut frame::Allocator,
+                  flush: &mut Flush)
+        -> Result<frame::Frame, Error>;
+
+    fn protect_page(&mut self,
+                    addr: &Addr,
+                    flags: Flags,
+                    size: PageSize,
+                    frame_allocator: &mut frame::Allocator,
+                    flush: &mut Flush)
+        -> Result<(), Error>;
+
+    fn translate(&self, addr: &Addr) -> Option<(Entry, PageSize)>;
+
+    fn release_entries(&mut self,
+                       first: usize,
+                       last: usize,
+                       frame_allocator: &mut frame::Allocator);
+
+    fn for_each_leaf(&self,
+                     prefix: usize,
+                     first: usize,
+                     last: usize,
+                     f: &mut dyn FnMut(Addr, &Entry, PageSize));
+
     fn map_frame(&mut self,
                  addr: &Addr,
                  entry: Entry,
-                 frame_allocator: &mut frame::Allocator)
-        -> Result<(), AllocError>;
+                 frame_allocator: &mut frame::Allocator,
+                 flush: &mut Flush)
+        -> Result<(), Error> {
+        self.map_page(addr, entry, PageSize::Small, frame_allocator, flush)
+    }
 
-    fn unmap_frame(&mut self, addr: &Addr) -> Result<frame::Frame, AllocError>;
+    fn unmap_frame(&mut self,
+                   addr: &Addr,
+                   frame_allocator: &mut frame::Allocator,
+                   flush: &mut Flush)
+        -> Result<frame::Frame, Error> {
+        self.unmap_page(addr, PageSize::Small, frame_allocator, flush)
+    }
 }
 
 macro_rules! table_struct {
@@ -46,63 +135,283 @@ macro_rules! impl_table {
                 }
             }
 
+            pub fn is_empty(&self) -> bool {
+                unsafe { (*self.entries).iter().all(|entry| *entry == 0) }
+            }
+
             pub fn set_entry(&mut self, i: usize, entry: Entry) {
                 unsafe {
                     (*self.entries)[i] = entry.value();
                 }
             }
+
+            pub fn child_addr(&self, i: usize) -> Addr {
+                let mut addr = Addr::new(((self.entries as usize) << 9 | i << 12)
+                    & 0o000000_777_777_777_777_0000);
+                addr.to_valid();
+                addr
+            }
+
+            pub fn get_entry(&self, i: usize) -> Entry {
+                let value = unsafe { (*self.entries)[i] };
+                if self.level > 1 && value & Flags::HUGE.bits() != 0 {
+                    Entry::from_huge_entry(value)
+                } else {
+                    Entry::from_entry(value)
+                }
+            }
         }
     }
 }
 
 macro_rules! impl_table_level {
     ($T:tt, $U:tt) => {
+        impl $T {
+            fn down_level(&self, addr: &Addr) -> $U {
+                $U::new(&addr.get_table_addr(self.level - 1, self.base), self.base)
+            }
+
+            fn child(&self, i: usize) -> $U {
+                $U::new(&self.child_addr(i), self.base)
+            }
+
+            pub fn allocate_entry(&mut self,
+                i: usize,
+                flags: Flags,
+                frame_allocator: &mut frame::Allocator)
+                -> Result<(), Error> {
+                let current_entry = self.get_entry(i);
+                if !current_entry.unused() {
+                    if !current_entry.flags.contains(flags) && !current_entry.is_huge() {
+                        self.set_entry(i, Entry::new(current_entry.addr,
+                                current_entry.flags | flags));
+                    }
+                    return Ok(());
+                }
+                let table_frame = match frame_allocator.alloc() {
+                    Ok(frame) => frame,
+                    Err(error) => return Err(Error::from(error))
+                };
+                self.set_entry(i, Entry::new(table_frame.base.addr,
+                        Flags::WRITABLE | Flags::PRESENT | flags));
+                let mut down_level = self.child(i);
+                unsafe {
+                    asm::x86_64::reg::tlb::invalidate(down_level.entries as usize);
+                }
+                down_level.flush(0, 511);
+                Ok(())
+            }
+
+            fn split(&mut self,
+                i: usize,
+                addr: &Addr,
+                frame_allocator: &mut frame::Allocator,
+                flush: &mut Flush)
+                -> Result<(), Error> {
+                let huge_entry = self.get_entry(i);
+                let table_frame = match frame_allocator.alloc() {
+                    Ok(frame) => frame,
+                    Err(error) => return Err(Error::from(error))
+                };
+                let flags = huge_entry.flags.to_small();
+                let child_size = 1 << (12 + 9 * (self.level - 2));
+                let level = self.level;
+                fill_table(self.base, table_frame.base.addr, &|j| {
+                    let child_addr = huge_entry.addr + j * child_size;
+                    match level {
+                        2 => Entry::new(child_addr, flags),
+                        _ => Entry::new_huge(child_addr, flags)
+                    }
+                });
+                self.set_entry(i, Entry::new(table_frame.base.addr,
+                        Flags::WRITABLE | Flags::PRESENT | (huge_entry.flags & Flags::USER)));
+                let down_level = self.down_level(addr);
+                unsafe {
+                    asm::x86_64::reg::tlb::invalidate(down_level.entries as usize);
+                }
+                flush.add(addr.addr);
+                Ok(())
+            }
+
+            fn walk(&mut self,
+                addr: &Addr,
+                size: PageSize,
+                frame_allocator: &mut frame::Allocator,
+                flush: &mut Flush)
+                -> Result<Option<$U>, Error> {
+                let i = addr.get_table_index(self.level);
+                let current_entry = self.get_entry(i);
+                if current_entry.unused() {
+                    return Err(Error::NotMapped(addr.addr));
+                }
+                if current_entry.is_huge() {
+                    if size.level() == self.level {
+                        return Ok(None);
+                    }
+                    if let Err(error) = self.split(i, addr, frame_allocator, flush) {
+                        return Err(error);
+                    }
+                } else if size.level() == self.level {
+                    return Err(Error::SizeMismatch(addr.addr));
+                } else if !current_entry.flags.contains(Flags::WRITABLE | Flags::PRESENT) {
+                    return Err(Error::Forbidden(addr.addr));
+                }
+                Ok(Some(self.down_level(addr)))
+            }
+
+            fn release(&mut self,
+                i: usize,
+                down_level: &$U,
+                frame_allocator: &mut frame::Allocator) {
+                if self.level == 4 && !address_space::is_user_slot(i) {
+                    return;
+                }
+                let table_frame = frame::Frame::new(self.get_entry(i).addr);
+                self.set_entry(i, Entry::new(0, Flags::NONE));
+                unsafe {
+                    asm::x86_64::reg::tlb::invalidate(down_level.entries as usize);
+                }
+                frame_allocator.dealloc(table_frame);
+            }
+        }
+
         impl TableLevel for $T {
             type DownLevel = $U;
 
-            fn map_frame(&mut self, 
+            fn map_page(&mut self,
                 addr: &Addr,
                 entry: Entry,
-                frame_allocator: &mut frame::Allocator)
-                -> Result<(), AllocError> {
+                size: PageSize,
+                frame_allocator: &mut frame::Allocator,
+                flush: &mut Flush)
+                -> Result<(), Error> {
                     let i = addr.get_table_index(self.level);
-                    let current_entry = unsafe { Entry::from_entry((*self.entries)[i]) };
-                    let mut do_flush = false;
-                    if current_entry.unused() {
-                        do_flush = true;
-                        match frame_allocator.alloc() {
-                            Ok(table_frame) => {
-                                self.set_entry(i, Entry::new(table_frame.base.addr,
-                                        entry::FLAG_WRITABLE
-                                        | entry::FLAG_PRESENT));
-                            },
-                            Err(error) => return Err(error)
+                    let current_entry = self.get_entry(i);
+                    if size.level() == self.level {
+                        return match current_entry.unused() {
+                            true => {
+                                self.set_entry(i, Entry::new_huge(entry.addr, entry.flags));
+                                Ok(())
+                            }
+                            false => Err(Error::AlreadyMapped(addr.addr))
                         };
-                    } else if current_entry.flags & entry::FLAG_WRITABLE == 0 
-                        || current_entry.flags & entry::FLAG_PRESENT == 0 {
-                        return Err(AllocError::Forbidden);
                     }
-                    let mut down_level = Self::DownLevel::new(
-                        &addr.get_table_addr(self.level - 1, self.base), self.base);
-                    if do_flush {
-                        down_level.flush(0, 511);
+                    if current_entry.is_huge() {
+                        return Err(Error::HugePageConflict(addr.addr));
+                    } else if !current_entry.unused()
+                        && !current_entry.flags.contains(Flags::WRITABLE | Flags::PRESENT) {
+                        return Err(Error::Forbidden(addr.addr));
+                    }
+                    if let Err(error) = self.allocate_entry(i, entry.flags & Flags::USER,
+                        frame_allocator) {
+                        return Err(error);
                     }
-                    down_level.map_frame(addr, entry, frame_allocator)
+                    let mut down_level = self.down_level(addr);
+                    down_level.map_page(addr, entry, size, frame_allocator, flush)
                 }
 
-            fn unmap_frame(&mut self, addr: &Addr) -> Result<frame::Frame, AllocError> {
-                let i = addr.get_table_index(self.level);
-                let current_entry = unsafe { Entry::from_entry((*self.entries)[i]) };
+            fn unmap_page(&mut self,
+                addr: &Addr,
+                size: PageSize,
+                frame_allocator: &mut frame::Allocator,
+                flush: &mut Flush)
+                -> Result<frame::Frame, Error> {
+                match self.walk(addr, size, frame_allocator, flush) {
+                    Ok(Some(mut down_level)) => {
+                        let frame = down_level.unmap_page(addr, size, frame_allocator, flush);
+                        if frame.is_ok() && down_level.is_empty() {
+                            self.release(addr.get_table_index(self.level), &down_level,
+                                frame_allocator);
+                        }
+                        frame
+                    },
+                    Ok(None) => {
+                        let i = addr.get_table_index(self.level);
+                        let frame = frame::Frame::new(self.get_entry(i).addr);
+                        self.set_entry(i, Entry::new(0, Flags::NONE));
+                        flush.add(addr.addr);
+                        Ok(frame)
+                    },
+                    Err(error) => Err(error)
+                }
+            }
+
+            fn protect_page(&mut self,
+                addr: &Addr,
+                flags: Flags,
+                size: PageSize,
+                frame_allocator: &mut frame::Allocator,
+                flush: &mut Flush)
+                -> Result<(), Error> {
+                match self.walk(addr, size, frame_allocator, flush) {
+                    Ok(Some(mut down_level)) => {
+                        down_level.protect_page(addr, flags, size, frame_allocator, flush)
+                    },
+                    Ok(None) => {
+                        let i = addr.get_table_index(self.level);
+                        let current_entry = self.get_entry(i);
+                        self.set_entry(i, Entry::new_huge(current_entry.addr,
+                                flags | (current_entry.flags & fault::OWNED)));
+                        flush.add(addr.addr);
+                        Ok(())
+                    },
+                    Err(error) => Err(error)
+                }
+            }
+
+            fn translate(&self, addr: &Addr) -> Option<(Entry, PageSize)> {
+                let current_entry = self.get_entry(addr.get_table_index(self.level));
                 if current_entry.unused() {
-                    return Err(AllocError::InvalidAddr);
+                    None
+                } else if current_entry.is_huge() {
+                    Some((current_entry, PageSize::from_level(self.level)))
+                } else {
+                    self.down_level(addr).translate(addr)
                 }
-                else if current_entry.flags & entry::FLAG_WRITABLE == 0 
-                    || current_entry.flags & entry::FLAG_PRESENT == 0 {
-                        return Err(AllocError::Forbidden);
+            }
+
+            fn release_entries(&mut self,
+                first: usize,
+                last: usize,
+                frame_allocator: &mut frame::Allocator) {
+                for i in first..=last {
+                    let current_entry = self.get_entry(i);
+                    if current_entry.unused() {
+                        continue;
+                    }
+                    if current_entry.is_huge() {
+                        if current_entry.flags.contains(fault::OWNED) {
+                            frame_allocator.dealloc_range(frame::Frame::new(current_entry.addr),
+                                PageSize::from_level(self.level).size() / frame::FRAME_SIZE);
+                        }
+                    } else {
+                        self.child(i).release_entries(0, 511, frame_allocator);
+                        frame_allocator.dealloc(frame::Frame::new(current_entry.addr));
+                    }
+                    self.set_entry(i, Entry::new(0, Flags::NONE));
+                }
+            }
+
+            fn for_each_leaf(&self,
+                prefix: usize,
+                first: usize,
+                last: usize,
+                f: &mut dyn FnMut(Addr, &Entry, PageSize)) {
+                let span = 1 << (12 + 9 * (self.level - 1));
+                for i in first..=last {
+                    let current_entry = self.get_entry(i);
+                    if current_entry.unused() {
+                        continue;
+                    }
+                    let virt = prefix + i * span;
+                    if current_entry.is_huge() {
+                        let mut addr = Addr::new(virt);
+                        addr.to_valid();
+                        f(addr, &current_entry, PageSize::from_level(self.level));
+                    } else {
+                        self.child(i).for_each_leaf(virt, 0, 511, f);
+                    }
                 }
-                let mut down_level = Self::DownLevel::new(
-                    &addr.get_table_addr(self.level - 1, self.base), self.base);
-                down_level.unmap_frame(addr)
             }
         }
     };
@@ -110,38 +419,133 @@ macro_rules! impl_table_level {
         impl TableLevel for $T {
             type DownLevel = $T;
 
-            fn map_frame(&mut self, 
+            fn map_page(&mut self,
                 addr: &Addr,
                 entry: Entry,
-                _frame_allocator: &mut frame::Allocator)
-                -> Result<(), AllocError> {
+                size: PageSize,
+                _frame_allocator: &mut frame::Allocator,
+                _flush: &mut Flush)
+                -> Result<(), Error> {
+                    if size != PageSize::Small {
+                        return Err(Error::SizeMismatch(addr.addr));
+                    }
                     let i = addr.get_table_index(self.level);
-                    let current_entry = unsafe { Entry::from_entry((*self.entries)[i]) };
+                    let current_entry = self.get_entry(i);
                     match current_entry.unused() {
                         true => {
                             self.set_entry(i, entry);
                             Ok(())
                         }
-                        false => Err(AllocError::InUse)
+                        false => Err(Error::AlreadyMapped(addr.addr))
                     }
             }
 
-            fn unmap_frame(&mut self, addr: &Addr) -> Result<frame::Frame, AllocError> {
+            fn unmap_page(&mut self,
+                addr: &Addr,
+                size: PageSize,
+                _frame_allocator: &mut frame::Allocator,
+                flush: &mut Flush)
+                -> Result<frame::Frame, Error> {
+                    if size != PageSize::Small {
+                        return Err(Error::SizeMismatch(addr.addr));
+                    }
                     let i = addr.get_table_index(self.level);
-                    let current_entry = unsafe { Entry::from_entry((*self.entries)[i]) };
+                    let current_entry = self.get_entry(i);
                     match current_entry.unused() {
                         false => {
                             let frame = frame::Frame::new(current_entry.addr);
-                            self.set_entry(i, Entry::new(0, 0));
+                            self.set_entry(i, Entry::new(0, Flags::NONE));
+                            flush.add(addr.addr);
                             Ok(frame)
                         }
-                        true => Err(AllocError::InvalidAddr)
+                        true => Err(Error::NotMapped(addr.addr))
+                    }
+            }
+
+            fn protect_page(&mut self,
+                addr: &Addr,
+                flags: Flags,
+                size: PageSize,
+                _frame_allocator: &mut frame::Allocator,
+                flush: &mut Flush)
+                -> Result<(), Error> {
+                    if size != PageSize::Small {
+                        return Err(Error::SizeMismatch(addr.addr));
+                    }
+                    let i = addr.get_table_index(self.level);
+                    let current_entry = self.get_entry(i);
+                    match current_entry.unused() {
+                        false => {
+                            self.set_entry(i, Entry::new(current_entry.addr,
+                                    flags | (current_entry.flags & fault::OWNED)));
+                            flush.add(addr.addr);
+                            Ok(())
+                        }
+                        true => Err(Error::NotMapped(addr.addr))
+                    }
+            }
+
+            fn translate(&self, addr: &Addr) -> Option<(Entry, PageSize)> {
+                let current_entry = self.get_entry(addr.get_table_index(self.level));
+                match current_entry.unused() {
+                    true => None,
+                    false => Some((current_entry, PageSize::Small))
+                }
+            }
+
+            fn release_entries(&mut self,
+                first: usize,
+                last: usize,
+                frame_allocator: &mut frame::Allocator) {
+                for i in first..=last {
+                    let current_entry = self.get_entry(i);
+                    if current_entry.unused() {
+                        continue;
+                    }
+                    if current_entry.flags.contains(Flags::PRESENT | fault::OWNED) {
+                        fault::release_frame(frame_allocator,
+                            frame::Frame::new(current_entry.addr), current_entry.flags);
                     }
+                    self.set_entry(i, Entry::new(0, Flags::NONE));
+                }
+            }
+
+            fn for_each_leaf(&self,
+                prefix: usize,
+                first: usize,
+                last: usize,
+                f: &mut dyn FnMut(Addr, &Entry, PageSize)) {
+                for i in first..=last {
+                    let current_entry = self.get_entry(i);
+                    if !current_entry.unused() {
+                        let mut addr = Addr::new(prefix + i * frame::FRAME_SIZE);
+                        addr.to_valid();
+                        f(addr, &current_entry, PageSize::Small);
+                    }
+                }
             }
         }
     };
 }
 
+// Writes a table that is not linked yet through the scratch slot of the
+// hierarchy, so that the range it will cover never maps a half-built table.
+fn fill_table(base: usize, table: usize, entry: &dyn Fn(usize) -> Entry) {
+    let pml4 = PML4::new(&address_space::pml4_addr(base), base);
+    let slot = address_space::SCRATCH_SLOT;
+    let entries = pml4.child_addr(slot).addr as *mut [usize; 512];
+    unsafe {
+        let scratch = (*pml4.entries)[slot];
+        (*pml4.entries)[slot] = Entry::new(table, Flags::WRITABLE | Flags::PRESENT).value();
+        asm::x86_64::reg::tlb::invalidate(entries as usize);
+        for j in 0..512 {
+            (*entries)[j] = entry(j).value();
+        }
+        (*pml4.entries)[slot] = scratch;
+        asm::x86_64::reg::tlb::invalidate(entries as usize);
+    }
+}
+
 macro_rules! builder {
     ($T:tt, $U:tt, $level:tt) => {
         table_struct!($T);
@@ -0,0 +1,53 @@
+const MAX_PENDING: usize = 32;
+
+pub struct Flush {
+    pending: [usize; MAX_PENDING],
+    count: usize,
+    full: bool
+}
+
+impl Flush {
+    pub const fn new() -> Flush {
+        Flush {
+            pending: [0; MAX_PENDING],
+            count: 0,
+            full: false
+        }
+    }
+
+    pub fn add(&mut self, addr: usize) {
+        if self.full {
+            return;
+        }
+        if self.count == MAX_PENDING {
+            self.full = true;
+        } else {
+            self.pending[self.count] = addr;
+            self.count += 1;
+        }
+    }
+
+    pub fn add_all(&mut self) {
+        self.full = true;
+    }
+
+    pub fn apply(&mut self) {
+        unsafe {
+            if self.full {
+                asm::x86_64::reg::tlb::flush();
+            } else {
+                for addr in self.pending[..self.count].iter() {
+                    asm::x86_64::reg::tlb::invalidate(*addr);
+                }
+            }
+        }
+        self.count = 0;
+        self.full = false;
+    }
+}
+
+impl Drop for Flush {
+    fn drop(&mut self) {
+        self.apply();
+    }
+}
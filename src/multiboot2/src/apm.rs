@@ -0,0 +1,32 @@
+pub struct Info {
+    pub version: u16,
+    pub cseg: u16,
+    pub offset: u32,
+    pub cseg_16: u16,
+    pub dseg: u16,
+    pub flags: u16,
+    pub cseg_len: u16,
+    pub cseg_16_len: u16,
+    pub dseg_len: u16
+}
+
+impl Info {
+    pub fn new(tag: &super::Tag) -> Info {
+        if tag.tag_type != super::TagType::Apm as u32 {
+            panic!("Invalid 'APM' tag");
+        }
+        unsafe {
+            Info {
+                version: *((tag.addr + 8) as *const u16),
+                cseg: *((tag.addr + 10) as *const u16),
+                offset: *((tag.addr + 12) as *const u32),
+                cseg_16: *((tag.addr + 16) as *const u16),
+                dseg: *((tag.addr + 18) as *const u16),
+                flags: *((tag.addr + 20) as *const u16),
+                cseg_len: *((tag.addr + 22) as *const u16),
+                cseg_16_len: *((tag.addr + 24) as *const u16),
+                dseg_len: *((tag.addr + 26) as *const u16)
+            }
+        }
+    }
+}
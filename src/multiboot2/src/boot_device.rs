@@ -0,0 +1,20 @@
+pub const NONE: u32 = 0xffff_ffff;
+
+pub struct Info {
+    pub biosdev: u32,
+    pub partition: u32,
+    pub sub_partition: u32
+}
+
+impl Info {
+    pub fn new(tag: &super::Tag) -> Info {
+        if tag.tag_type != super::TagType::BootDevice as u32 {
+            panic!("Invalid 'BIOS boot device' tag");
+        }
+        Info {
+            biosdev: unsafe { *((tag.addr + 8) as *const u32) },
+            partition: unsafe { *((tag.addr + 12) as *const u32) },
+            sub_partition: unsafe { *((tag.addr + 16) as *const u32) }
+        }
+    }
+}
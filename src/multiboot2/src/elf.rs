@@ -2,24 +2,68 @@ pub const SHF_WRITE: usize = 0x1;
 pub const SHF_ALLOC: usize = 0x2;
 pub const SHF_EXECINSTR: usize = 0x4;
 
+pub const SHT_NULL: u32 = 0;
+pub const SHT_PROGBITS: u32 = 1;
+pub const SHT_SYMTAB: u32 = 2;
+pub const SHT_STRTAB: u32 = 3;
+pub const SHT_NOBITS: u32 = 8;
+
+pub const STT_NOTYPE: u8 = 0;
+pub const STT_OBJECT: u8 = 1;
+pub const STT_FUNC: u8 = 2;
+pub const STT_SECTION: u8 = 3;
+pub const STT_FILE: u8 = 4;
+
+pub const STB_LOCAL: u8 = 0;
+pub const STB_GLOBAL: u8 = 1;
+pub const STB_WEAK: u8 = 2;
+
+#[derive(Copy, Clone)]
 pub struct Header {
     addr: usize,
     num: u32,
     entsize: u32,
-    _shndx: u32
+    shndx: u32
 }
 
 pub struct SectionIter {
-    entsize: u32,
-    count: i16,
-    addr: usize
+    header: Header,
+    index: u32
 }
 
+#[derive(Copy, Clone)]
 pub struct Section {
-    pub sh_type: usize,
+    pub sh_name: u32,
+    pub sh_type: u32,
     pub sh_flags: usize,
     pub sh_addr: usize,
-    pub sh_size: usize
+    pub sh_offset: usize,
+    pub sh_size: usize,
+    pub sh_link: u32,
+    pub sh_info: u32,
+    pub sh_addralign: usize,
+    pub sh_entsize: usize
+}
+
+#[derive(Copy, Clone)]
+pub struct SymbolTable {
+    symbols: Section,
+    strings: Section
+}
+
+pub struct SymbolIter {
+    table: SymbolTable,
+    index: usize
+}
+
+#[derive(Copy, Clone)]
+pub struct Symbol {
+    pub st_name: u32,
+    pub st_info: u8,
+    pub st_other: u8,
+    pub st_shndx: u16,
+    pub st_value: usize,
+    pub st_size: usize
 }
 
 impl Header {
@@ -29,16 +73,48 @@ impl Header {
                 addr: tag.addr,
                 num: *((tag.addr + 8) as *const u32),
                 entsize: *((tag.addr + 12) as *const u32),
-                _shndx: *((tag.addr + 16) as *const u32)
+                shndx: *((tag.addr + 16) as *const u32)
             }
         }
     }
 
+    pub fn len(&self) -> usize {
+        self.num as usize
+    }
+
+    pub fn section(&self, index: u32) -> Option<Section> {
+        match index < self.num {
+            true => Some(Section::new(self.addr + 20 + (index * self.entsize) as usize)),
+            false => None
+        }
+    }
+
     pub fn sections(&self) -> SectionIter {
         SectionIter {
-            count: self.num as i16,
-            addr: self.addr + 20,
-            entsize: self.entsize
+            header: *self,
+            index: 0
+        }
+    }
+
+    pub fn name(&self, section: &Section) -> Option<&'static str> {
+        match self.section(self.shndx) {
+            Some(strings) => strings.string(section.sh_name),
+            None => None
+        }
+    }
+
+    pub fn find(&self, name: &str) -> Option<Section> {
+        self.sections().find(|section| self.name(section) == Some(name))
+    }
+
+    pub fn symbol_table(&self) -> Option<SymbolTable> {
+        let symbols = match self.sections().find(|section| section.sh_type == SHT_SYMTAB) {
+            Some(section) => section,
+            None => return None
+        };
+        match self.section(symbols.sh_link) {
+            Some(strings) => SymbolTable::new(symbols, strings),
+            None => None
         }
     }
 }
@@ -47,24 +123,138 @@ impl Iterator for SectionIter {
     type Item = Section;
 
     fn next(&mut self) -> Option<Self::Item> {
-        match self.count > 0 {
-             true => {
+        let section = self.header.section(self.index);
+        if section.is_some() {
+            self.index += 1;
+        }
+        section
+    }
+}
+
+impl Section {
+    fn new(addr: usize) -> Section {
+        unsafe {
+            Section {
+                sh_name: *(addr as *const u32),
+                sh_type: *((addr + 0x04) as *const u32),
+                sh_flags: *((addr + 0x08) as *const usize),
+                sh_addr: *((addr + 0x10) as *const usize),
+                sh_offset: *((addr + 0x18) as *const usize),
+                sh_size: *((addr + 0x20) as *const usize),
+                sh_link: *((addr + 0x28) as *const u32),
+                sh_info: *((addr + 0x2c) as *const u32),
+                sh_addralign: *((addr + 0x30) as *const usize),
+                sh_entsize: *((addr + 0x38) as *const usize)
+            }
+        }
+    }
+
+    pub fn is_allocated(&self) -> bool {
+        self.sh_flags & SHF_ALLOC != 0
+    }
+
+    pub fn is_symbolic(&self) -> bool {
+        !self.is_allocated() && self.sh_addr != 0 && self.sh_size != 0
+            && (self.sh_type == SHT_SYMTAB || self.sh_type == SHT_STRTAB)
+    }
+
+    pub fn string(&self, offset: u32) -> Option<&'static str> {
+        let offset = offset as usize;
+        if self.sh_type != SHT_STRTAB || self.sh_addr == 0 || offset >= self.sh_size {
+            return None;
+        }
+        let start = (self.sh_addr + offset) as *const u8;
+        let mut len = 0;
+        while offset + len < self.sh_size && unsafe { *start.add(len) } != 0 {
+            len += 1;
+        }
+        let bytes = unsafe { core::slice::from_raw_parts(start, len) };
+        match core::str::from_utf8(bytes) {
+            Ok(string) => Some(string),
+            Err(_) => None
+        }
+    }
+}
+
+impl SymbolTable {
+    pub fn new(symbols: Section, strings: Section) -> Option<SymbolTable> {
+        if symbols.sh_type != SHT_SYMTAB || symbols.sh_addr == 0 || symbols.sh_entsize == 0 {
+            return None;
+        }
+        Some(SymbolTable {
+            symbols: symbols,
+            strings: strings
+        })
+    }
+
+    pub fn symbols(&self) -> SymbolIter {
+        SymbolIter {
+            table: *self,
+            index: 0
+        }
+    }
+
+    pub fn name(&self, symbol: &Symbol) -> Option<&'static str> {
+        self.strings.string(symbol.st_name)
+    }
+
+    pub fn resolve(&self, addr: usize) -> Option<(&'static str, usize)> {
+        let mut best: Option<Symbol> = None;
+        for symbol in self.symbols().filter(|symbol| symbol.kind() == STT_FUNC) {
+            if symbol.st_value > addr {
+                continue;
+            }
+            if symbol.contains(addr) {
+                best = Some(symbol);
+                break;
+            }
+            match best {
+                Some(current) if current.st_value >= symbol.st_value => {},
+                _ => best = Some(symbol)
+            }
+        }
+        match best {
+            Some(symbol) => Some((self.name(&symbol).unwrap_or("?"), addr - symbol.st_value)),
+            None => None
+        }
+    }
+}
+
+impl Iterator for SymbolIter {
+    type Item = Symbol;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let symbols = &self.table.symbols;
+        match self.index < symbols.sh_size / symbols.sh_entsize {
+            true => {
+                let addr = symbols.sh_addr + self.index * symbols.sh_entsize;
+                self.index += 1;
                 unsafe {
-                    let section = Section {
-                        sh_type: *((self.addr + 0x04) as *const usize),
-                        sh_flags: *((self.addr + 0x08) as *const usize),
-                        sh_addr: *((self.addr + 0x10) as *const usize),
-                        sh_size: *((self.addr + 0x20) as *const usize)
-                    };
-                    self.count -= 1;
-                    self.addr += self.entsize as usize;
-                    match section.sh_flags {
-                        0x00 => self.next(),
-                        _ => Some(section)
-                    }
+                    Some(Symbol {
+                        st_name: *(addr as *const u32),
+                        st_info: *((addr + 0x04) as *const u8),
+                        st_other: *((addr + 0x05) as *const u8),
+                        st_shndx: *((addr + 0x06) as *const u16),
+                        st_value: *((addr + 0x08) as *const usize),
+                        st_size: *((addr + 0x10) as *const usize)
+                    })
                 }
             },
             false => None
         }
     }
 }
+
+impl Symbol {
+    pub fn kind(&self) -> u8 {
+        self.st_info & 0xf
+    }
+
+    pub fn binding(&self) -> u8 {
+        self.st_info >> 4
+    }
+
+    pub fn contains(&self, addr: usize) -> bool {
+        addr >= self.st_value && addr < self.st_value + self.st_size
+    }
+}
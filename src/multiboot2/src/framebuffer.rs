@@ -0,0 +1,79 @@
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct Color {
+    pub red: u8,
+    pub green: u8,
+    pub blue: u8
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct Field {
+    pub position: u8,
+    pub size: u8
+}
+
+#[derive(Copy, Clone, Debug)]
+pub enum Kind {
+    Indexed(&'static [Color]),
+    Rgb {
+        red: Field,
+        green: Field,
+        blue: Field
+    },
+    Text,
+    Unknown(u8)
+}
+
+pub struct Info {
+    pub addr: u64,
+    pub pitch: u32,
+    pub width: u32,
+    pub height: u32,
+    pub bpp: u8,
+    pub kind: Kind
+}
+
+impl Field {
+    fn new(addr: usize) -> Field {
+        Field {
+            position: unsafe { *(addr as *const u8) },
+            size: unsafe { *((addr + 1) as *const u8) }
+        }
+    }
+}
+
+impl Info {
+    pub fn new(tag: &super::Tag) -> Info {
+        if tag.tag_type != super::TagType::Framebuffer as u32 {
+            panic!("Invalid 'framebuffer' tag");
+        }
+        let kind = match unsafe { *((tag.addr + 29) as *const u8) } {
+            0 => {
+                let count = unsafe { *((tag.addr + 32) as *const u16) } as usize;
+                let max = (tag.size as usize).saturating_sub(34) / 3;
+                Kind::Indexed(unsafe {
+                    core::slice::from_raw_parts((tag.addr + 34) as *const Color, count.min(max))
+                })
+            },
+            1 => Kind::Rgb {
+                red: Field::new(tag.addr + 32),
+                green: Field::new(tag.addr + 34),
+                blue: Field::new(tag.addr + 36)
+            },
+            2 => Kind::Text,
+            kind => Kind::Unknown(kind)
+        };
+        Info {
+            addr: unsafe { *((tag.addr + 8) as *const u64) },
+            pitch: unsafe { *((tag.addr + 16) as *const u32) },
+            width: unsafe { *((tag.addr + 20) as *const u32) },
+            height: unsafe { *((tag.addr + 24) as *const u32) },
+            bpp: unsafe { *((tag.addr + 28) as *const u8) },
+            kind: kind
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        self.pitch as usize * self.height as usize
+    }
+}
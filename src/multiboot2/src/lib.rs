@@ -1,9 +1,15 @@
 #![no_std]
 
 mod basic_mem_info;
-mod mem_map;
+pub mod apm;
+pub mod boot_device;
 pub mod elf;
+pub mod framebuffer;
+pub mod mem_map;
+pub mod module;
+pub mod rsdp;
 
+#[derive(Copy, Clone)]
 pub struct Info {
     pub base: usize,
     pub total_size: u32
@@ -15,16 +21,36 @@ pub struct Tag {
     size: u32
 }
 
-struct TagIter {
-    addr: usize
+pub struct TagIter {
+    addr: usize,
+    end: usize
 }
 
 #[repr(u32)]
-#[derive(Copy, Clone)]
-enum TagType {
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum TagType {
+    End = 0,
+    CommandLine = 1,
+    BootloaderName = 2,
+    Module = 3,
     BasicMemInfo = 4,
+    BootDevice = 5,
     MemMap = 6,
-    Elf = 9
+    Vbe = 7,
+    Framebuffer = 8,
+    Elf = 9,
+    Apm = 10,
+    Efi32 = 11,
+    Efi64 = 12,
+    Smbios = 13,
+    RsdpV1 = 14,
+    RsdpV2 = 15,
+    Network = 16,
+    EfiMemMap = 17,
+    EfiBootServices = 18,
+    Efi32ImageHandle = 19,
+    Efi64ImageHandle = 20,
+    LoadBaseAddr = 21
 }
 
 impl Info {
@@ -35,12 +61,31 @@ impl Info {
         }
     }
 
-    fn tags(&self) -> TagIter {
+    pub fn tags(&self) -> TagIter {
         TagIter {
-            addr: self.base + 8
+            addr: self.base + 8,
+            end: self.base + self.total_size as usize
+        }
+    }
+
+    pub fn get_command_line(&self) -> Option<&'static str> {
+        match self.tags().find(TagType::CommandLine) {
+            Some(tag) => Some(tag.string(8)),
+            None => None
         }
     }
 
+    pub fn get_bootloader_name(&self) -> Option<&'static str> {
+        match self.tags().find(TagType::BootloaderName) {
+            Some(tag) => Some(tag.string(8)),
+            None => None
+        }
+    }
+
+    pub fn get_modules(&self) -> module::ModuleIter {
+        module::ModuleIter::new(self.tags())
+    }
+
     pub fn get_basic_mem_info(&self) -> Option<basic_mem_info::Info> {
         match self.tags().find(TagType::BasicMemInfo) {
             Some(tag) => Some(basic_mem_info::Info::new(&tag)),
@@ -48,19 +93,119 @@ impl Info {
         }
     }
 
+    pub fn get_boot_device(&self) -> Option<boot_device::Info> {
+        match self.tags().find(TagType::BootDevice) {
+            Some(tag) => Some(boot_device::Info::new(&tag)),
+            None => None
+        }
+    }
+
     pub fn get_mem_map(&self) -> Option<mem_map::Info> {
         match self.tags().find(TagType::MemMap) {
             Some(tag) => Some(mem_map::Info::new(&tag)),
             None => None
         }
     }
-    
-    pub fn get_elf_sections(&self) -> Option<elf::SectionIter> {
+
+    pub fn get_framebuffer(&self) -> Option<framebuffer::Info> {
+        match self.tags().find(TagType::Framebuffer) {
+            Some(tag) => Some(framebuffer::Info::new(&tag)),
+            None => None
+        }
+    }
+
+    pub fn get_elf_header(&self) -> Option<elf::Header> {
         match self.tags().find(TagType::Elf) {
-            Some(tag) => Some(elf::Header::new(&tag).sections()),
+            Some(tag) => Some(elf::Header::new(&tag)),
+            None => None
+        }
+    }
+
+    pub fn get_elf_sections(&self) -> Option<elf::SectionIter> {
+        match self.get_elf_header() {
+            Some(header) => Some(header.sections()),
             None => None
         }
     }
+
+    pub fn get_symbol_table(&self) -> Option<elf::SymbolTable> {
+        match self.get_elf_header() {
+            Some(header) => header.symbol_table(),
+            None => None
+        }
+    }
+
+    pub fn get_apm(&self) -> Option<apm::Info> {
+        match self.tags().find(TagType::Apm) {
+            Some(tag) => Some(apm::Info::new(&tag)),
+            None => None
+        }
+    }
+
+    pub fn get_efi32_system_table(&self) -> Option<u32> {
+        match self.tags().find(TagType::Efi32) {
+            Some(tag) => Some(unsafe { *((tag.addr + 8) as *const u32) }),
+            None => None
+        }
+    }
+
+    pub fn get_efi64_system_table(&self) -> Option<u64> {
+        match self.tags().find(TagType::Efi64) {
+            Some(tag) => Some(unsafe { *((tag.addr + 8) as *const u64) }),
+            None => None
+        }
+    }
+
+    pub fn get_rsdp_v1(&self) -> Option<rsdp::Rsdp> {
+        match self.tags().find(TagType::RsdpV1) {
+            Some(tag) => Some(rsdp::Rsdp::new(&tag)),
+            None => None
+        }
+    }
+
+    pub fn get_rsdp_v2(&self) -> Option<rsdp::Rsdp> {
+        match self.tags().find(TagType::RsdpV2) {
+            Some(tag) => Some(rsdp::Rsdp::new(&tag)),
+            None => None
+        }
+    }
+
+    pub fn get_rsdp(&self) -> Option<rsdp::Rsdp> {
+        match self.get_rsdp_v2() {
+            Some(rsdp) => Some(rsdp),
+            None => self.get_rsdp_v1()
+        }
+    }
+}
+
+impl TagType {
+    pub fn from_u32(tag_type: u32) -> Option<TagType> {
+        match tag_type {
+            0 => Some(TagType::End),
+            1 => Some(TagType::CommandLine),
+            2 => Some(TagType::BootloaderName),
+            3 => Some(TagType::Module),
+            4 => Some(TagType::BasicMemInfo),
+            5 => Some(TagType::BootDevice),
+            6 => Some(TagType::MemMap),
+            7 => Some(TagType::Vbe),
+            8 => Some(TagType::Framebuffer),
+            9 => Some(TagType::Elf),
+            10 => Some(TagType::Apm),
+            11 => Some(TagType::Efi32),
+            12 => Some(TagType::Efi64),
+            13 => Some(TagType::Smbios),
+            14 => Some(TagType::RsdpV1),
+            15 => Some(TagType::RsdpV2),
+            16 => Some(TagType::Network),
+            17 => Some(TagType::EfiMemMap),
+            18 => Some(TagType::EfiBootServices),
+            19 => Some(TagType::Efi32ImageHandle),
+            20 => Some(TagType::Efi64ImageHandle),
+            21 => Some(TagType::LoadBaseAddr),
+            _ => None
+        }
+    }
 }
 
 impl Tag {
@@ -71,6 +216,39 @@ impl Tag {
             size: unsafe { *((tag_addr + 4) as *const u32) }
         }
     }
+
+    pub fn addr(&self) -> usize {
+        self.addr
+    }
+
+    pub fn tag_type(&self) -> u32 {
+        self.tag_type
+    }
+
+    pub fn kind(&self) -> Option<TagType> {
+        TagType::from_u32(self.tag_type)
+    }
+
+    pub fn size(&self) -> u32 {
+        self.size
+    }
+
+    pub fn data(&self) -> &'static [u8] {
+        let len = (self.size as usize).saturating_sub(8);
+        unsafe { core::slice::from_raw_parts((self.addr + 8) as *const u8, len) }
+    }
+
+    fn string(&self, offset: usize) -> &'static str {
+        let data = match self.data().get(offset - 8..) {
+            Some(data) => data,
+            None => return ""
+        };
+        let len = data.iter().position(|byte| *byte == 0).unwrap_or(data.len());
+        match core::str::from_utf8(&data[..len]) {
+            Ok(string) => string,
+            Err(_) => ""
+        }
+    }
 }
 
 impl TagIter {
@@ -88,18 +266,19 @@ impl Iterator for TagIter {
     type Item = Tag;
 
     fn next(&mut self) -> Option<Tag> {
-        unsafe {
-            match *(self.addr as *const u32) {
-                0 => None,
-                _ => {
-                    let tag = Tag::new(self.addr);
-                    self.addr += tag.size as usize;
-                    if self.addr % 8 != 0 {
-                        self.addr += 8 - self.addr % 8;
-                    }
-                    Some(tag)
-                }
-            }
+        if self.addr + 8 > self.end {
+            return None;
+        }
+        let tag = Tag::new(self.addr);
+        if tag.tag_type == TagType::End as u32 || tag.size < 8
+            || self.addr + tag.size as usize > self.end {
+            self.addr = self.end;
+            return None;
+        }
+        self.addr += tag.size as usize;
+        if self.addr % 8 != 0 {
+            self.addr += 8 - self.addr % 8;
         }
+        Some(tag)
     }
 }
@@ -1,3 +1,5 @@
+pub const AVAILABLE: u32 = 1;
+
 pub struct Info {
     addr: usize,
     size: u32,
@@ -39,6 +41,12 @@ impl Info {
     }
 }
 
+impl Entry {
+    pub fn is_available(&self) -> bool {
+        self.entry_type == AVAILABLE
+    }
+}
+
 impl<'a> Iterator for InfoIter<'a> {
     type Item = Entry;
 
@@ -0,0 +1,45 @@
+pub struct Module {
+    pub start: u32,
+    pub end: u32,
+    pub cmdline: &'static str
+}
+
+pub struct ModuleIter {
+    tags: super::TagIter
+}
+
+impl Module {
+    pub fn new(tag: &super::Tag) -> Module {
+        if tag.tag_type != super::TagType::Module as u32 {
+            panic!("Invalid 'module' tag");
+        }
+        Module {
+            start: unsafe { *((tag.addr + 8) as *const u32) },
+            end: unsafe { *((tag.addr + 12) as *const u32) },
+            cmdline: tag.string(16)
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.end.saturating_sub(self.start) as usize
+    }
+}
+
+impl ModuleIter {
+    pub fn new(tags: super::TagIter) -> ModuleIter {
+        ModuleIter {
+            tags: tags
+        }
+    }
+}
+
+impl Iterator for ModuleIter {
+    type Item = Module;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.tags.find(super::TagType::Module) {
+            Some(tag) => Some(Module::new(&tag)),
+            None => None
+        }
+    }
+}
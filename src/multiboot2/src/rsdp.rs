@@ -0,0 +1,71 @@
+pub const SIGNATURE: &[u8; 8] = b"RSD PTR ";
+
+const V1_LENGTH: usize = 20;
+
+pub struct Rsdp {
+    pub signature: [u8; 8],
+    pub checksum: u8,
+    pub oem_id: [u8; 6],
+    pub revision: u8,
+    pub rsdt_addr: u32,
+    pub length: u32,
+    pub xsdt_addr: Option<u64>,
+    valid: bool
+}
+
+impl Rsdp {
+    pub fn new(tag: &super::Tag) -> Rsdp {
+        let extended = match tag.tag_type {
+            tag_type if tag_type == super::TagType::RsdpV1 as u32 => false,
+            tag_type if tag_type == super::TagType::RsdpV2 as u32 => true,
+            _ => panic!("Invalid 'ACPI RSDP' tag")
+        };
+        let data = tag.data();
+        let mut rsdp = Rsdp {
+            signature: [0; 8],
+            checksum: 0,
+            oem_id: [0; 6],
+            revision: 0,
+            rsdt_addr: 0,
+            length: V1_LENGTH as u32,
+            xsdt_addr: None,
+            valid: false
+        };
+        if data.len() < V1_LENGTH {
+            return rsdp;
+        }
+        rsdp.signature.copy_from_slice(&data[0..8]);
+        rsdp.checksum = data[8];
+        rsdp.oem_id.copy_from_slice(&data[9..15]);
+        rsdp.revision = data[15];
+        rsdp.rsdt_addr = read_u32(&data[16..20]);
+        rsdp.valid = &rsdp.signature == SIGNATURE && sum(&data[..V1_LENGTH]) == 0;
+        if extended && rsdp.revision >= 2 && data.len() >= 36 {
+            rsdp.length = read_u32(&data[20..24]);
+            let length = (rsdp.length as usize).min(data.len());
+            rsdp.xsdt_addr = Some(read_u32(&data[24..28]) as u64
+                | (read_u32(&data[28..32]) as u64) << 32);
+            rsdp.valid = rsdp.valid && sum(&data[..length]) == 0;
+        }
+        rsdp
+    }
+
+    pub fn oem_id(&self) -> &str {
+        match core::str::from_utf8(&self.oem_id) {
+            Ok(oem_id) => oem_id.trim_end(),
+            Err(_) => "?"
+        }
+    }
+
+    pub fn is_valid(&self) -> bool {
+        self.valid
+    }
+}
+
+fn read_u32(bytes: &[u8]) -> u32 {
+    bytes.iter().rev().fold(0, |value, byte| value << 8 | *byte as u32)
+}
+
+fn sum(bytes: &[u8]) -> u8 {
+    bytes.iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte))
+}
@@ -0,0 +1,181 @@
+#![no_std]
+
+use asm::x86_64::mmio;
+use idt::controller::Controller;
+use idt::interrupts;
+
+use spinlock::Mutex;
+
+pub static PIC: Pic = Pic::new();
+
+pub const MASTER_OFFSET: u8 = 32;
+pub const SLAVE_OFFSET: u8 = 40;
+pub const CASCADE_LINE: u8 = 2;
+
+const MASTER_COMMAND: usize = 0x20;
+const MASTER_DATA: usize = 0x21;
+const SLAVE_COMMAND: usize = 0xa0;
+const SLAVE_DATA: usize = 0xa1;
+const WAIT_PORT: usize = 0x80;
+
+const ICW1_INIT: u8 = 0x10;
+const ICW1_ICW4: u8 = 0x01;
+const ICW4_8086: u8 = 0x01;
+const OCW3_READ_ISR: u8 = 0x0b;
+const EOI: u8 = 0x20;
+
+const SPURIOUS_LINE: u8 = 7;
+
+struct Chip {
+    command: usize,
+    data: usize,
+    offset: u8
+}
+
+impl Chip {
+    const fn new(command: usize, data: usize, offset: u8) -> Chip {
+        Chip {
+            command: command,
+            data: data,
+            offset: offset
+        }
+    }
+
+    fn handles(&self, vector: usize) -> bool {
+        vector >= self.offset as usize && vector < self.offset as usize + 8
+    }
+
+    unsafe fn command(&self, value: u8) {
+        mmio::Port::new(self.command).write(value);
+        io_wait();
+    }
+
+    unsafe fn read_mask(&self) -> u8 {
+        mmio::Port::new(self.data).read()
+    }
+
+    unsafe fn write_mask(&self, mask: u8) {
+        mmio::Port::new(self.data).write(mask);
+    }
+
+    unsafe fn write_data(&self, value: u8) {
+        mmio::Port::new(self.data).write(value);
+        io_wait();
+    }
+
+    unsafe fn in_service(&self) -> u8 {
+        mmio::Port::new(self.command).write(OCW3_READ_ISR);
+        mmio::Port::new(self.command).read()
+    }
+
+    unsafe fn eoi(&self) {
+        mmio::Port::new(self.command).write(EOI);
+    }
+}
+
+pub struct Pic {
+    master: Chip,
+    slave: Chip,
+    mutex: Mutex<()>
+}
+
+impl Pic {
+    pub const fn new() -> Pic {
+        Pic {
+            master: Chip::new(MASTER_COMMAND, MASTER_DATA, MASTER_OFFSET),
+            slave: Chip::new(SLAVE_COMMAND, SLAVE_DATA, SLAVE_OFFSET),
+            mutex: Mutex::new(())
+        }
+    }
+
+    pub fn init(&self) {
+        interrupts::without(|| {
+            let _lock = self.mutex.lock();
+            unsafe {
+                self.master.command(ICW1_INIT | ICW1_ICW4);
+                self.slave.command(ICW1_INIT | ICW1_ICW4);
+                self.master.write_data(self.master.offset);
+                self.slave.write_data(self.slave.offset);
+                self.master.write_data(1 << CASCADE_LINE);
+                self.slave.write_data(CASCADE_LINE);
+                self.master.write_data(ICW4_8086);
+                self.slave.write_data(ICW4_8086);
+                self.master.write_mask(!(1 << CASCADE_LINE));
+                self.slave.write_mask(0xff);
+            }
+        })
+    }
+
+    pub fn disable(&self) {
+        interrupts::without(|| {
+            let _lock = self.mutex.lock();
+            unsafe {
+                self.master.write_mask(0xff);
+                self.slave.write_mask(0xff);
+            }
+        })
+    }
+
+    fn chip(&self, line: u8) -> (&Chip, u8) {
+        match line < 8 {
+            true => (&self.master, line),
+            false => (&self.slave, line - 8)
+        }
+    }
+}
+
+impl Controller for Pic {
+    fn vector(&self, line: u8) -> usize {
+        MASTER_OFFSET as usize + line as usize
+    }
+
+    fn mask(&self, line: u8) {
+        interrupts::without(|| {
+            let _lock = self.mutex.lock();
+            let (chip, bit) = self.chip(line);
+            unsafe {
+                chip.write_mask(chip.read_mask() | 1 << bit);
+            }
+        })
+    }
+
+    fn unmask(&self, line: u8) {
+        interrupts::without(|| {
+            let _lock = self.mutex.lock();
+            let (chip, bit) = self.chip(line);
+            unsafe {
+                chip.write_mask(chip.read_mask() & !(1 << bit));
+            }
+        })
+    }
+
+    fn eoi(&self, vector: usize) {
+        unsafe {
+            if self.slave.handles(vector) {
+                self.slave.eoi();
+            }
+            if self.master.handles(vector) || self.slave.handles(vector) {
+                self.master.eoi();
+            }
+        }
+    }
+
+    fn is_spurious(&self, vector: usize) -> bool {
+        let _lock = self.mutex.lock();
+        unsafe {
+            if vector == self.master.offset as usize + SPURIOUS_LINE as usize {
+                return self.master.in_service() & 1 << SPURIOUS_LINE == 0;
+            }
+            if vector == self.slave.offset as usize + SPURIOUS_LINE as usize
+                && self.slave.in_service() & 1 << SPURIOUS_LINE == 0 {
+                self.master.eoi();
+                return true;
+            }
+        }
+        false
+    }
+}
+
+unsafe fn io_wait() {
+    mmio::Port::new(WAIT_PORT).write(0);
+}
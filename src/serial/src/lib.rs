@@ -1,58 +1,281 @@
 #![no_std]
 
+mod ring;
+
+use crate::ring::ByteRing;
+
 use asm::x86_64::mmio;
+use idt::{IDT, StackFrame};
+use idt::interrupts;
+use idt::irq::IrqError;
+
+use spinlock::Mutex;
+
+use core::fmt;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+const BASE_BAUD: u32 = 115200;
+const FIFO_SIZE: usize = 16;
+
+const IER_RX_AVAILABLE: u8 = 1 << 0;
+const IER_TX_EMPTY: u8 = 1 << 1;
+
+const IIR_NO_INTERRUPT: u8 = 1 << 0;
+const IIR_ID_MASK: u8 = 0b110;
+const IIR_MODEM_STATUS: u8 = 0b000;
+const IIR_TX_EMPTY: u8 = 0b010;
+const IIR_RX_AVAILABLE: u8 = 0b100;
+const IIR_LINE_STATUS: u8 = 0b110;
+
+const LCR_DLAB: u8 = 1 << 7;
+
+const MCR_DTR_RTS: u8 = 0x03;
+const MCR_OUT2: u8 = 1 << 3;
+
+const LSR_DATA_READY: u8 = 1 << 0;
+const LSR_TX_EMPTY: u8 = 1 << 5;
 
 #[repr(u16)]
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, PartialEq)]
 pub enum ComAddr {
-    Com1 = 0x3F8
+    Com1 = 0x3F8,
+    Com2 = 0x2F8,
+    Com3 = 0x3E8,
+    Com4 = 0x2E8
 }
 
+impl ComAddr {
+    pub fn irq_line(&self) -> u8 {
+        match self {
+            ComAddr::Com1 | ComAddr::Com3 => 4,
+            ComAddr::Com2 | ComAddr::Com4 => 3
+        }
+    }
+
+    fn index(&self) -> usize {
+        match self {
+            ComAddr::Com1 => 0,
+            ComAddr::Com2 => 1,
+            ComAddr::Com3 => 2,
+            ComAddr::Com4 => 3
+        }
+    }
+}
+
+#[repr(u8)]
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum DataBits {
+    Five = 0b00,
+    Six = 0b01,
+    Seven = 0b10,
+    Eight = 0b11
+}
+
+#[repr(u8)]
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Parity {
+    None = 0b000_000,
+    Odd = 0b001_000,
+    Even = 0b011_000,
+    Mark = 0b101_000,
+    Space = 0b111_000
+}
+
+#[repr(u8)]
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum StopBits {
+    One = 0b000,
+    Two = 0b100
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Config {
+    pub baud: u32,
+    pub data_bits: DataBits,
+    pub parity: Parity,
+    pub stop_bits: StopBits
+}
+
+impl Config {
+    pub const fn new() -> Config {
+        Config {
+            baud: 38400,
+            data_bits: DataBits::Eight,
+            parity: Parity::None,
+            stop_bits: StopBits::One
+        }
+    }
+
+    fn divisor(&self) -> u16 {
+        match self.baud {
+            0 => 1,
+            baud if baud >= BASE_BAUD => 1,
+            baud => (BASE_BAUD / baud) as u16
+        }
+    }
+
+    fn line_control(&self) -> u8 {
+        self.data_bits as u8 | self.parity as u8 | self.stop_bits as u8
+    }
+}
+
+#[derive(Debug)]
+pub enum SerialError {
+    InvalidBaud(u32),
+    Irq(IrqError)
+}
+
+struct Device {
+    interrupts: AtomicBool,
+    rx: ByteRing,
+    tx: ByteRing,
+    tx_lock: Mutex<()>
+}
+
+impl Device {
+    const fn new() -> Device {
+        Device {
+            interrupts: AtomicBool::new(false),
+            rx: ByteRing::new(),
+            tx: ByteRing::new(),
+            tx_lock: Mutex::new(())
+        }
+    }
+}
+
+static DEVICES: [Device; 4] = [Device::new(), Device::new(), Device::new(), Device::new()];
+static LINES: Mutex<[bool; 2]> = Mutex::new([false; 2]);
+
 pub struct Port {
+    addr: ComAddr,
     data: mmio::Port,
     irq_enable: mmio::Port,
     irq_id: mmio::Port,
     line_control: mmio::Port,
     modem_control: mmio::Port,
     line_status: mmio::Port,
-    _modem_status: mmio::Port,
+    modem_status: mmio::Port,
     _scratch: mmio::Port
 }
 
 impl Port {
     pub unsafe fn new(port: ComAddr) -> Port {
-        let serial = Port {
+        let serial = Port::open(port);
+        serial.init(&Config::new());
+        serial
+    }
+
+    pub unsafe fn with_config(port: ComAddr, config: &Config) -> Result<Port, SerialError> {
+        if config.baud == 0 || config.baud > BASE_BAUD || BASE_BAUD % config.baud != 0 {
+            return Err(SerialError::InvalidBaud(config.baud));
+        }
+        let serial = Port::open(port);
+        serial.init(config);
+        Ok(serial)
+    }
+
+    fn open(port: ComAddr) -> Port {
+        Port {
+            addr: port,
             data: mmio::Port::new(port as usize),
             irq_enable: mmio::Port::new(port as usize + 1),
             irq_id: mmio::Port::new(port as usize + 2),
             line_control: mmio::Port::new(port as usize + 3),
             modem_control: mmio::Port::new(port as usize + 4),
             line_status: mmio::Port::new(port as usize + 5),
-            _modem_status: mmio::Port::new(port as usize + 6),
+            modem_status: mmio::Port::new(port as usize + 6),
             _scratch: mmio::Port::new(port as usize + 7)
-        };
-        serial.init();
-        serial
+        }
     }
 
-    unsafe fn init(&self) {
-        self.irq_enable.write(0x00 as u8);
-        self.line_control.write(0x80 as u8);
-        self.data.write(0x03 as u8);
+    unsafe fn init(&self, config: &Config) {
+        let divisor = config.divisor();
+        self.device().interrupts.store(false, Ordering::Release);
         self.irq_enable.write(0x00 as u8);
-        self.line_control.write(0x03 as u8);
+        self.line_control.write(LCR_DLAB);
+        self.data.write(divisor as u8);
+        self.irq_enable.write((divisor >> 8) as u8);
+        self.line_control.write(config.line_control());
         self.irq_id.write(0xC7 as u8);
         self.modem_control.write(0x0B as u8);
     }
 
+    fn device(&self) -> &'static Device {
+        &DEVICES[self.addr.index()]
+    }
+
+    fn is_interrupt_driven(&self) -> bool {
+        self.device().interrupts.load(Ordering::Acquire)
+    }
+
+    pub fn enable_interrupts(&self) -> Result<(), SerialError> {
+        let line = self.addr.irq_line();
+        let registered = interrupts::without(|| {
+            let mut lines = LINES.lock();
+            let registered = &mut lines[(line - 3) as usize];
+            if *registered {
+                return Ok(());
+            }
+            match IDT.register_line(line, true, move |sf| handle_irq(line, sf)) {
+                Ok(_) => {
+                    *registered = true;
+                    Ok(())
+                },
+                Err(error) => Err(SerialError::Irq(error))
+            }
+        });
+        if let Err(error) = registered {
+            return Err(error);
+        }
+        self.device().interrupts.store(true, Ordering::Release);
+        unsafe {
+            self.modem_control.write(MCR_DTR_RTS | MCR_OUT2);
+            self.irq_enable.write(IER_RX_AVAILABLE);
+        }
+        Ok(())
+    }
+
     pub unsafe fn read(&self) -> u8 {
-        while self.line_status.read() & 0x01 == 0 {}
+        if self.is_interrupt_driven() {
+            loop {
+                if let Some(byte) = self.device().rx.pop() {
+                    return byte;
+                }
+                asm::x86_64::instruction::hlt();
+            }
+        }
+        while self.line_status.read() & LSR_DATA_READY == 0 {}
         self.data.read()
     }
 
+    pub fn try_read(&self) -> Option<u8> {
+        if self.is_interrupt_driven() {
+            return self.device().rx.pop();
+        }
+        unsafe {
+            match self.line_status.read() & LSR_DATA_READY {
+                0 => None,
+                _ => Some(self.data.read())
+            }
+        }
+    }
+
     pub unsafe fn write(&self, byte: u8) {
-        while self.line_status.read() & 0x20 == 0 {}
-        self.data.write(byte);
+        if !self.is_interrupt_driven() {
+            while self.line_status.read() & LSR_TX_EMPTY == 0 {}
+            self.data.write(byte);
+            return;
+        }
+        interrupts::without(|| {
+            let _lock = self.device().tx_lock.lock();
+            if !self.device().tx.push(byte) {
+                self.drain();
+                while !self.device().tx.push(byte) {
+                    self.drain();
+                }
+            }
+            self.irq_enable.write(IER_RX_AVAILABLE | IER_TX_EMPTY);
+        });
     }
 
     pub unsafe fn write_str(&self, s: &str) {
@@ -60,4 +283,116 @@ impl Port {
             self.write(byte);
         }
     }
+
+    unsafe fn drain(&self) {
+        while self.line_status.read() & LSR_TX_EMPTY == 0 {}
+        for _ in 0..FIFO_SIZE {
+            match self.device().tx.pop() {
+                Some(byte) => self.data.write(byte),
+                None => break
+            }
+        }
+    }
+
+    unsafe fn handle_interrupt(&self) {
+        loop {
+            let id = self.irq_id.read();
+            if id & IIR_NO_INTERRUPT != 0 {
+                return;
+            }
+            match id & IIR_ID_MASK {
+                IIR_RX_AVAILABLE => {
+                    while self.line_status.read() & LSR_DATA_READY != 0 {
+                        self.device().rx.push(self.data.read());
+                    }
+                },
+                IIR_TX_EMPTY => {
+                    self.drain();
+                    if self.device().tx.is_empty() {
+                        self.irq_enable.write(IER_RX_AVAILABLE);
+                    }
+                },
+                IIR_LINE_STATUS => {
+                    self.line_status.read();
+                },
+                IIR_MODEM_STATUS => {
+                    self.modem_status.read();
+                },
+                _ => return
+            }
+        }
+    }
+}
+
+pub struct PolledWriter {
+    port: Port
+}
+
+impl PolledWriter {
+    pub unsafe fn new(port: ComAddr) -> PolledWriter {
+        let writer = PolledWriter {
+            port: Port::open(port)
+        };
+        while let Some(byte) = writer.port.device().tx.pop() {
+            writer.write(byte);
+        }
+        writer
+    }
+
+    unsafe fn write(&self, byte: u8) {
+        while self.port.line_status.read() & LSR_TX_EMPTY == 0 {}
+        self.port.data.write(byte);
+    }
+}
+
+impl fmt::Write for PolledWriter {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for byte in s.bytes() {
+            unsafe {
+                self.write(byte);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Write for Port {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        unsafe {
+            Port::write_str(self, s);
+        }
+        Ok(())
+    }
+}
+
+pub struct SerialSink {
+    port: ComAddr
+}
+
+impl SerialSink {
+    pub const fn new(port: ComAddr) -> SerialSink {
+        SerialSink {
+            port: port
+        }
+    }
+}
+
+impl log::Sink for SerialSink {
+    fn log(&self, record: &log::Record) {
+        let _ = record.write_to(&mut Port::open(self.port));
+    }
+}
+
+fn handle_irq(line: u8, _sf: &mut StackFrame) {
+    for addr in [ComAddr::Com1, ComAddr::Com2, ComAddr::Com3, ComAddr::Com4].iter() {
+        if addr.irq_line() != line {
+            continue;
+        }
+        let port = Port::open(*addr);
+        if port.is_interrupt_driven() {
+            unsafe {
+                port.handle_interrupt();
+            }
+        }
+    }
 }
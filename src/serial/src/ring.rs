@@ -0,0 +1,49 @@
+use core::cell::UnsafeCell;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+pub const CAPACITY: usize = 1024;
+
+pub struct ByteRing {
+    buffer: UnsafeCell<[u8; CAPACITY]>,
+    head: AtomicUsize,
+    tail: AtomicUsize
+}
+
+impl ByteRing {
+    pub const fn new() -> ByteRing {
+        ByteRing {
+            buffer: UnsafeCell::new([0; CAPACITY]),
+            head: AtomicUsize::new(0),
+            tail: AtomicUsize::new(0)
+        }
+    }
+
+    pub fn push(&self, byte: u8) -> bool {
+        let tail = self.tail.load(Ordering::Relaxed);
+        let next = (tail + 1) % CAPACITY;
+        if next == self.head.load(Ordering::Acquire) {
+            return false;
+        }
+        unsafe {
+            (*self.buffer.get())[tail] = byte;
+        }
+        self.tail.store(next, Ordering::Release);
+        true
+    }
+
+    pub fn pop(&self) -> Option<u8> {
+        let head = self.head.load(Ordering::Relaxed);
+        if head == self.tail.load(Ordering::Acquire) {
+            return None;
+        }
+        let byte = unsafe { (*self.buffer.get())[head] };
+        self.head.store((head + 1) % CAPACITY, Ordering::Release);
+        Some(byte)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.head.load(Ordering::Acquire) == self.tail.load(Ordering::Acquire)
+    }
+}
+
+unsafe impl Sync for ByteRing {}
@@ -30,6 +30,16 @@ impl<T: Sized> Mutex<T> {
             content: unsafe { &mut *self.content.get() }
         }
     }
+
+    pub fn try_lock(&self) -> Option<Guard<T>> {
+        match self.lock.compare_and_swap(false, true, Ordering::Acquire) {
+            true => None,
+            false => Some(Guard {
+                lock: & self.lock,
+                content: unsafe { &mut *self.content.get() }
+            })
+        }
+    }
 }
 
 impl<'a, T: Sized> Drop for Guard<'a, T> {
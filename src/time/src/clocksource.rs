@@ -0,0 +1,158 @@
+use crate::hpet::Hpet;
+use crate::tsc;
+
+use core::ops::{Add, Sub};
+use core::sync::atomic::{AtomicBool, Ordering};
+use core::time::Duration;
+
+// Written once by init before READY is set, then only read, so that the clock
+// can be read from interrupt handlers without taking a lock.
+static mut SOURCE: Source = Source::Ticks;
+static INITIALIZED: AtomicBool = AtomicBool::new(false);
+static READY: AtomicBool = AtomicBool::new(false);
+
+const NANOS: u128 = 1_000_000_000;
+
+pub enum Source {
+    Ticks,
+    Tsc(u64),
+    Hpet(Hpet)
+}
+
+impl Source {
+    fn nanos(&self) -> u64 {
+        match self {
+            Source::Ticks => crate::uptime().as_nanos() as u64,
+            Source::Tsc(hz) => (tsc::read() as u128 * NANOS / *hz as u128) as u64,
+            Source::Hpet(hpet) => (hpet.counter() as u128 * NANOS / hpet.frequency() as u128)
+                as u64
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Source::Ticks => "ticks",
+            Source::Tsc(_) => "tsc",
+            Source::Hpet(_) => "hpet"
+        }
+    }
+}
+
+pub fn init(hpet_base: Option<usize>) -> &'static str {
+    if INITIALIZED.swap(true, Ordering::Acquire) {
+        return current().name();
+    }
+    let hpet = match hpet_base {
+        Some(base) => match Hpet::new(base) {
+            Ok(hpet) if hpet.frequency() != 0 => Some(hpet),
+            _ => None
+        },
+        None => None
+    };
+    // A 32-bit HPET wraps after a few minutes, so it is only used to calibrate.
+    let source = match (tsc::is_supported(), tsc::is_invariant(), hpet) {
+        (true, true, Some(hpet)) => Source::Tsc(calibrate(&hpet)),
+        (_, _, Some(hpet)) if hpet.is_wide() => Source::Hpet(hpet),
+        (true, _, Some(hpet)) => Source::Tsc(calibrate(&hpet)),
+        (true, _, None) => Source::Tsc(tsc::calibrate_with_pit()),
+        (false, _, _) => Source::Ticks
+    };
+    unsafe {
+        SOURCE = source;
+    }
+    READY.store(true, Ordering::Release);
+    current().name()
+}
+
+fn calibrate(hpet: &Hpet) -> u64 {
+    tsc::calibrate_with(&|| hpet.counter(), hpet.frequency(), hpet.mask())
+}
+
+fn current() -> &'static Source {
+    match READY.load(Ordering::Acquire) {
+        true => unsafe { &SOURCE },
+        false => &Source::Ticks
+    }
+}
+
+pub fn now_nanos() -> u64 {
+    current().nanos()
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Instant {
+    nanos: u64
+}
+
+impl Instant {
+    pub fn now() -> Instant {
+        Instant {
+            nanos: now_nanos()
+        }
+    }
+
+    pub fn duration_since(&self, earlier: Instant) -> Duration {
+        Duration::from_nanos(self.nanos.saturating_sub(earlier.nanos))
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        Instant::now().duration_since(*self)
+    }
+
+    pub fn as_nanos(&self) -> u64 {
+        self.nanos
+    }
+
+    pub fn checked_add(&self, duration: Duration) -> Option<Instant> {
+        match self.nanos.checked_add(nanos(duration)) {
+            Some(nanos) => Some(Instant {
+                nanos: nanos
+            }),
+            None => None
+        }
+    }
+
+    pub fn checked_sub(&self, duration: Duration) -> Option<Instant> {
+        match self.nanos.checked_sub(nanos(duration)) {
+            Some(nanos) => Some(Instant {
+                nanos: nanos
+            }),
+            None => None
+        }
+    }
+}
+
+fn nanos(duration: Duration) -> u64 {
+    match duration.as_nanos() {
+        nanos if nanos > u64::max_value() as u128 => u64::max_value(),
+        nanos => nanos as u64
+    }
+}
+
+impl Add<Duration> for Instant {
+    type Output = Instant;
+
+    fn add(self, other: Duration) -> Instant {
+        Instant {
+            nanos: self.nanos.saturating_add(nanos(other))
+        }
+    }
+}
+
+impl Sub<Duration> for Instant {
+    type Output = Instant;
+
+    fn sub(self, other: Duration) -> Instant {
+        Instant {
+            nanos: self.nanos.saturating_sub(nanos(other))
+        }
+    }
+}
+
+impl Sub<Instant> for Instant {
+    type Output = Duration;
+
+    fn sub(self, other: Instant) -> Duration {
+        self.duration_since(other)
+    }
+}
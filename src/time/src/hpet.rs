@@ -0,0 +1,69 @@
+use mem::paging;
+
+use core::ptr;
+
+const CAPABILITIES: usize = 0x000;
+const CONFIGURATION: usize = 0x010;
+const MAIN_COUNTER: usize = 0x0f0;
+
+const BIT_ENABLE: u64 = 1 << 0;
+const BIT_COUNT_SIZE: u64 = 1 << 13;
+const FEMTOSECONDS: u64 = 1_000_000_000_000_000;
+
+#[derive(Copy, Clone)]
+pub struct Hpet {
+    base: usize,
+    period: u64,
+    wide: bool
+}
+
+impl Hpet {
+    pub fn new(phys_base: usize) -> Result<Hpet, paging::Error> {
+        let base = match mem::mmio::map(phys_base, 0x400) {
+            Ok(base) => base,
+            Err(error) => return Err(error)
+        };
+        let mut hpet = Hpet {
+            base: base,
+            period: 0,
+            wide: false
+        };
+        unsafe {
+            let capabilities = hpet.read(CAPABILITIES);
+            hpet.period = capabilities >> 32;
+            hpet.wide = capabilities & BIT_COUNT_SIZE != 0;
+            hpet.write(CONFIGURATION, hpet.read(CONFIGURATION) | BIT_ENABLE);
+        }
+        Ok(hpet)
+    }
+
+    unsafe fn read(&self, register: usize) -> u64 {
+        ptr::read_volatile((self.base + register) as *const u64)
+    }
+
+    unsafe fn write(&self, register: usize, value: u64) {
+        ptr::write_volatile((self.base + register) as *mut u64, value);
+    }
+
+    pub fn frequency(&self) -> u64 {
+        match self.period {
+            0 => 0,
+            period => FEMTOSECONDS / period
+        }
+    }
+
+    pub fn is_wide(&self) -> bool {
+        self.wide
+    }
+
+    pub fn mask(&self) -> u64 {
+        match self.wide {
+            true => u64::max_value(),
+            false => u32::max_value() as u64
+        }
+    }
+
+    pub fn counter(&self) -> u64 {
+        unsafe { self.read(MAIN_COUNTER) }
+    }
+}
@@ -0,0 +1,132 @@
+#![no_std]
+
+pub mod pit;
+pub mod tsc;
+pub mod hpet;
+pub mod clocksource;
+pub mod rtc;
+
+pub use crate::clocksource::Instant;
+
+use idt::{IDT, StackFrame};
+use idt::interrupts;
+use idt::irq::IrqError;
+
+use spinlock::Mutex;
+
+use core::sync::atomic::{self, AtomicU64, Ordering};
+use core::time::Duration;
+
+pub const DEFAULT_FREQUENCY: u32 = 1000;
+
+const MAX_TIMERS: usize = 32;
+
+static TICKS: AtomicU64 = AtomicU64::new(0);
+static FREQUENCY: AtomicU64 = AtomicU64::new(0);
+static NEXT_TIMER: AtomicU64 = AtomicU64::new(1);
+static TIMERS: Mutex<[Option<Timer>; MAX_TIMERS]> = Mutex::new([None; MAX_TIMERS]);
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct TimerId(u64);
+
+#[derive(Debug)]
+pub enum TimerError {
+    Full
+}
+
+#[derive(Copy, Clone)]
+struct Timer {
+    id: TimerId,
+    deadline: u64,
+    callback: fn()
+}
+
+pub fn init(frequency: u32) -> Result<(), IrqError> {
+    let actual = pit::set_frequency(frequency);
+    FREQUENCY.store(actual as u64, Ordering::SeqCst);
+    match IDT.register_line(pit::IRQ_LINE, false, tick) {
+        Ok(_) => Ok(()),
+        Err(error) => Err(error)
+    }
+}
+
+fn tick(_sf: &mut StackFrame) {
+    let now = TICKS.fetch_add(1, Ordering::SeqCst) + 1;
+    let mut expired: [Option<fn()>; MAX_TIMERS] = [None; MAX_TIMERS];
+    {
+        let mut timers = TIMERS.lock();
+        for (slot, timer) in timers.iter_mut().enumerate() {
+            if let Some(current) = *timer {
+                if current.deadline <= now {
+                    expired[slot] = Some(current.callback);
+                    *timer = None;
+                }
+            }
+        }
+    }
+    for callback in expired.iter() {
+        if let Some(callback) = callback {
+            callback();
+        }
+    }
+}
+
+pub fn ticks() -> u64 {
+    TICKS.load(Ordering::SeqCst)
+}
+
+pub fn frequency() -> u64 {
+    FREQUENCY.load(Ordering::SeqCst)
+}
+
+pub fn ticks_to_duration(ticks: u64) -> Duration {
+    match frequency() {
+        0 => Duration::from_secs(0),
+        frequency => Duration::from_nanos(
+            (ticks as u128 * 1_000_000_000 / frequency as u128) as u64)
+    }
+}
+
+pub fn uptime() -> Duration {
+    ticks_to_duration(ticks())
+}
+
+pub fn sleep_ticks(count: u64) {
+    let target = ticks() + count;
+    while ticks() < target {
+        atomic::spin_loop_hint();
+    }
+}
+
+pub fn add_timer(delay: u64, callback: fn()) -> Result<TimerId, TimerError> {
+    let id = TimerId(NEXT_TIMER.fetch_add(1, Ordering::SeqCst));
+    interrupts::without(|| {
+        let mut timers = TIMERS.lock();
+        match timers.iter_mut().find(|timer| timer.is_none()) {
+            Some(slot) => {
+                *slot = Some(Timer {
+                    id: id,
+                    deadline: ticks() + delay,
+                    callback: callback
+                });
+                Ok(id)
+            },
+            None => Err(TimerError::Full)
+        }
+    })
+}
+
+pub fn cancel_timer(id: TimerId) -> bool {
+    interrupts::without(|| {
+        let mut timers = TIMERS.lock();
+        for timer in timers.iter_mut() {
+            if let Some(current) = *timer {
+                if current.id == id {
+                    *timer = None;
+                    return true;
+                }
+            }
+        }
+        false
+    })
+}
@@ -0,0 +1,79 @@
+use asm::x86_64::mmio;
+
+use spinlock::Mutex;
+
+pub const BASE_FREQUENCY: u32 = 1_193_182;
+pub const IRQ_LINE: u8 = 0;
+
+const CHANNEL_0: usize = 0x40;
+const CHANNEL_2: usize = 0x42;
+const COMMAND: usize = 0x43;
+const SPEAKER: usize = 0x61;
+
+const SPEAKER_GATE: u8 = 1 << 0;
+const SPEAKER_DATA: u8 = 1 << 1;
+const CHANNEL_2_OUTPUT: u8 = 1 << 5;
+
+const SELECT_CHANNEL_0: u8 = 0b00 << 6;
+const SELECT_CHANNEL_2: u8 = 0b10 << 6;
+const ACCESS_LOW_HIGH: u8 = 0b11 << 4;
+const ACCESS_LATCH: u8 = 0b00 << 4;
+const MODE_INTERRUPT_ON_COUNT: u8 = 0b000 << 1;
+const MODE_RATE_GENERATOR: u8 = 0b010 << 1;
+
+static LOCK: Mutex<()> = Mutex::new(());
+
+pub fn divisor(frequency: u32) -> u32 {
+    match frequency {
+        0 => 0x10000,
+        _ => {
+            let divisor = (BASE_FREQUENCY + frequency / 2) / frequency;
+            match divisor {
+                0 | 1 => 2,
+                divisor if divisor > 0x10000 => 0x10000,
+                divisor => divisor
+            }
+        }
+    }
+}
+
+pub fn set_frequency(frequency: u32) -> u32 {
+    let divisor = divisor(frequency);
+    let _lock = LOCK.lock();
+    unsafe {
+        mmio::Port::new(COMMAND).write(SELECT_CHANNEL_0 | ACCESS_LOW_HIGH | MODE_RATE_GENERATOR);
+        mmio::Port::new(CHANNEL_0).write(divisor as u8);
+        mmio::Port::new(CHANNEL_0).write((divisor >> 8) as u8);
+    }
+    BASE_FREQUENCY / divisor
+}
+
+pub fn count() -> u16 {
+    let _lock = LOCK.lock();
+    unsafe {
+        mmio::Port::new(COMMAND).write(SELECT_CHANNEL_0 | ACCESS_LATCH);
+        let low = mmio::Port::new(CHANNEL_0).read() as u16;
+        let high = mmio::Port::new(CHANNEL_0).read() as u16;
+        high << 8 | low
+    }
+}
+
+pub fn measure<F>(count: u16, mut sample: F) -> (u64, u64)
+    where F: FnMut() -> u64 {
+    let _lock = LOCK.lock();
+    unsafe {
+        let speaker = mmio::Port::new(SPEAKER);
+        let gate = speaker.read() & !(SPEAKER_GATE | SPEAKER_DATA);
+        speaker.write(gate);
+        mmio::Port::new(COMMAND).write(SELECT_CHANNEL_2 | ACCESS_LOW_HIGH
+            | MODE_INTERRUPT_ON_COUNT);
+        mmio::Port::new(CHANNEL_2).write(count as u8);
+        mmio::Port::new(CHANNEL_2).write((count >> 8) as u8);
+        speaker.write(gate | SPEAKER_GATE);
+        let start = sample();
+        while speaker.read() & CHANNEL_2_OUTPUT == 0 {}
+        let end = sample();
+        speaker.write(gate);
+        (start, end)
+    }
+}
@@ -0,0 +1,189 @@
+use asm::x86_64::mmio;
+
+use spinlock::Mutex;
+
+use core::fmt;
+use core::sync::atomic::{AtomicU64, AtomicU8, Ordering};
+
+const SELECT: usize = 0x70;
+const DATA: usize = 0x71;
+
+const SECONDS: u8 = 0x00;
+const MINUTES: u8 = 0x02;
+const HOURS: u8 = 0x04;
+const DAY: u8 = 0x07;
+const MONTH: u8 = 0x08;
+const YEAR: u8 = 0x09;
+const STATUS_A: u8 = 0x0a;
+const STATUS_B: u8 = 0x0b;
+
+const BIT_UPDATE_IN_PROGRESS: u8 = 1 << 7;
+const BIT_24_HOURS: u8 = 1 << 1;
+const BIT_BINARY: u8 = 1 << 2;
+const BIT_PM: u8 = 1 << 7;
+
+const CENTURY: u16 = 2000;
+const SECONDS_PER_DAY: u64 = 86400;
+
+static LOCK: Mutex<()> = Mutex::new(());
+static BOOT_TIME: AtomicU64 = AtomicU64::new(0);
+static CENTURY_REGISTER: AtomicU8 = AtomicU8::new(0);
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct DateTime {
+    pub year: u16,
+    pub month: u8,
+    pub day: u8,
+    pub hour: u8,
+    pub minute: u8,
+    pub second: u8
+}
+
+impl DateTime {
+    pub fn to_unix(&self) -> u64 {
+        let mut days = days_from_civil(self.year as i64, self.month as i64, self.day as i64);
+        if days < 0 {
+            days = 0;
+        }
+        days as u64 * SECONDS_PER_DAY
+            + self.hour as u64 * 3600
+            + self.minute as u64 * 60
+            + self.second as u64
+    }
+
+    pub fn from_unix(timestamp: u64) -> DateTime {
+        let days = (timestamp / SECONDS_PER_DAY) as i64;
+        let seconds = timestamp % SECONDS_PER_DAY;
+        let (year, month, day) = civil_from_days(days);
+        DateTime {
+            year: year as u16,
+            month: month as u8,
+            day: day as u8,
+            hour: (seconds / 3600) as u8,
+            minute: (seconds / 60 % 60) as u8,
+            second: (seconds % 60) as u8
+        }
+    }
+}
+
+impl fmt::Display for DateTime {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:04}-{:02}-{:02} {:02}:{:02}:{:02} UTC",
+            self.year, self.month, self.day, self.hour, self.minute, self.second)
+    }
+}
+
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = (if year >= 0 { year } else { year - 399 }) / 400;
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * (month + if month > 2 { -3 } else { 9 }) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let days = days + 719468;
+    let era = (if days >= 0 { days } else { days - 146096 }) / 146097;
+    let day_of_era = days - era * 146097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524
+        - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_part = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_part + 2) / 5 + 1;
+    let month = if month_part < 10 { month_part + 3 } else { month_part - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+unsafe fn read_register(register: u8) -> u8 {
+    mmio::Port::new(SELECT).write(register);
+    mmio::Port::new(DATA).read()
+}
+
+unsafe fn update_in_progress() -> bool {
+    read_register(STATUS_A) & BIT_UPDATE_IN_PROGRESS != 0
+}
+
+unsafe fn read_raw(century: u8) -> [u8; 7] {
+    while update_in_progress() {}
+    [
+        read_register(SECONDS),
+        read_register(MINUTES),
+        read_register(HOURS),
+        read_register(DAY),
+        read_register(MONTH),
+        read_register(YEAR),
+        match century {
+            0 => 0,
+            register => read_register(register)
+        }
+    ]
+}
+
+fn from_bcd(value: u8) -> u8 {
+    (value & 0x0f) + (value >> 4) * 10
+}
+
+pub fn read() -> DateTime {
+    let _lock = LOCK.lock();
+    let century = CENTURY_REGISTER.load(Ordering::SeqCst);
+    let (mut raw, status) = unsafe {
+        let mut raw = read_raw(century);
+        loop {
+            let again = read_raw(century);
+            if again == raw {
+                break;
+            }
+            raw = again;
+        }
+        (raw, read_register(STATUS_B))
+    };
+    let pm = raw[2] & BIT_PM != 0;
+    raw[2] &= !BIT_PM;
+    if status & BIT_BINARY == 0 {
+        for value in raw.iter_mut() {
+            *value = from_bcd(*value);
+        }
+    }
+    if status & BIT_24_HOURS == 0 {
+        raw[2] = match (raw[2], pm) {
+            (12, false) => 0,
+            (12, true) => 12,
+            (hour, true) => hour + 12,
+            (hour, false) => hour
+        };
+    }
+    let base = match century {
+        0 => CENTURY,
+        _ => raw[6] as u16 * 100
+    };
+    DateTime {
+        year: base + raw[5] as u16,
+        month: raw[4],
+        day: raw[3],
+        hour: raw[2],
+        minute: raw[1],
+        second: raw[0]
+    }
+}
+
+pub fn init(century_register: Option<u8>) -> DateTime {
+    CENTURY_REGISTER.store(century_register.unwrap_or(0), Ordering::SeqCst);
+    let date = read();
+    let uptime = crate::uptime().as_secs();
+    BOOT_TIME.store(date.to_unix().saturating_sub(uptime), Ordering::SeqCst);
+    date
+}
+
+pub fn boot_time() -> u64 {
+    BOOT_TIME.load(Ordering::SeqCst)
+}
+
+pub fn unix_time() -> u64 {
+    boot_time() + crate::uptime().as_secs()
+}
+
+pub fn now() -> DateTime {
+    DateTime::from_unix(unix_time())
+}
@@ -0,0 +1,32 @@
+use crate::pit;
+
+pub const CALIBRATION_MS: u64 = 10;
+
+pub fn is_supported() -> bool {
+    asm::x86_64::cpuid::has_tsc()
+}
+
+pub fn is_invariant() -> bool {
+    asm::x86_64::cpuid::has_invariant_tsc()
+}
+
+pub fn read() -> u64 {
+    unsafe { asm::x86_64::instruction::rdtsc() }
+}
+
+pub fn calibrate_with_pit() -> u64 {
+    let count = (pit::BASE_FREQUENCY as u64 * CALIBRATION_MS / 1000) as u16;
+    let (start, end) = pit::measure(count, read);
+    (end - start) * pit::BASE_FREQUENCY as u64 / count as u64
+}
+
+pub fn calibrate_with(reference: &dyn Fn() -> u64, reference_hz: u64, reference_mask: u64)
+    -> u64 {
+    let target = reference_hz * CALIBRATION_MS / 1000;
+    let reference_start = reference();
+    let elapsed = || reference().wrapping_sub(reference_start) & reference_mask;
+    let start = read();
+    while elapsed() < target {}
+    let end = read();
+    (end.wrapping_sub(start) as u128 * reference_hz as u128 / elapsed() as u128) as u64
+}
@@ -122,4 +122,5 @@ macro_rules! println {
 pub fn _print(args: core::fmt::Arguments) {
     use core::fmt::Write;
     TEXT_BUFFER.lock().write_fmt(args).unwrap();
+    kmsg::print(args);
 }